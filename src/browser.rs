@@ -0,0 +1,505 @@
+use crate::config::read_config;
+use crate::delete_archive::delete_archives;
+use crate::download::initiate_retrievals;
+use crate::index::{open_index, IndexedArchives};
+use crate::inventory::{
+    cached_inventory_age, describe_inventory_age, do_inventory, resolve_all_pending,
+    InventoryFilters, InventoryFormat,
+};
+use crate::keymap::{Command, Keymap};
+use crate::list_vaults::list_all_vaults;
+use crate::multipart_upload::do_multipart_upload;
+use crate::shared::{
+    abort, basmati_directory, enter_terminal, format_duration, get_jobs, human_size, is_interrupt,
+    scroll_page, ArchiveItem, Cursor, Events, InitiatedJob, JobType, Listing, Selectable,
+    Selection,
+};
+use crate::theme::Theme;
+use anyhow::anyhow;
+use aws_sdk_glacier::Client;
+use colored::Colorize;
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::{prelude::*, widgets::*};
+use std::fs;
+use std::time;
+
+impl Selectable for InitiatedJob {
+    fn id(&self) -> &str {
+        &self.job_id
+    }
+    fn label(&self) -> String {
+        format!(
+            "{:<10} {:<20} {:>8}  {}",
+            self.job_type.label(),
+            self.vault,
+            format_duration(chrono::Utc::now().timestamp() - self.timestamp),
+            self.output_path.as_deref().unwrap_or(&self.job_id)
+        )
+    }
+}
+
+/// The panes of the browser, in the order <Tab> goes through them
+#[derive(Clone, Copy, PartialEq)]
+enum Pane {
+    Vaults,
+    Archives,
+    Jobs,
+}
+
+impl Pane {
+    fn next(self) -> Pane {
+        match self {
+            Pane::Vaults => Pane::Archives,
+            Pane::Archives => Pane::Jobs,
+            Pane::Jobs => Pane::Vaults,
+        }
+    }
+}
+
+/// Text typed in the status line before uploading a file
+enum Prompt {
+    File(String),
+    Description { file: String, value: String },
+}
+
+/// Operations that print their own progress, the browser hands them the terminal while they run
+enum Action {
+    Inventory(String),
+    Download(String, Vec<ArchiveItem>),
+    Upload {
+        vault: String,
+        file: String,
+        description: String,
+    },
+    Delete(String, Vec<ArchiveItem>),
+    CollectJobs,
+}
+
+struct Browser {
+    vaults: Events<String>,
+    /// `None` while the highlighted vault has no cached inventory
+    archives: Option<IndexedArchives>,
+    selected: Selection<ArchiveItem>,
+    jobs: Events<InitiatedJob>,
+    focus: Pane,
+    prompt: Option<Prompt>,
+    status: String,
+    keys: Keymap,
+    theme: Theme,
+}
+
+/// Vaults that have a cached inventory, used when the vaults of the account can not be listed
+fn local_vaults() -> Vec<String> {
+    let mut vaults: Vec<String> = fs::read_dir(format!("{}/vault", basmati_directory()))
+        .map(|entries| {
+            entries
+                .filter_map(Result::ok)
+                .filter_map(|x| x.file_name().to_str().map(String::from))
+                .collect()
+        })
+        .unwrap_or_default();
+    vaults.sort();
+    vaults
+}
+
+impl Browser {
+    async fn new(client: &Client) -> Result<Browser, anyhow::Error> {
+        let config = read_config()?;
+        let (vaults, listing_error) = match list_all_vaults(client).await {
+            Ok(vaults) => (
+                vaults
+                    .iter()
+                    .filter_map(|x| x.vault_name().map(String::from))
+                    .collect(),
+                None,
+            ),
+            Err(reason) => (local_vaults(), Some(reason)),
+        };
+        let mut browser = Browser {
+            vaults: Events::new(vaults),
+            archives: None,
+            selected: Selection::default(),
+            jobs: Events::new(vec![]),
+            focus: Pane::Vaults,
+            prompt: None,
+            status: String::new(),
+            keys: Keymap::new(&config),
+            theme: Theme::new(&config)?,
+        };
+        browser.refresh().await;
+        if let Some(reason) = listing_error {
+            browser.status = format!("listing vaults failed, showing cached vaults - {}", reason);
+        }
+        Ok(browser)
+    }
+
+    fn vault(&mut self) -> Option<String> {
+        self.vaults.choose()
+    }
+
+    /// Archives are read from the index a page at a time, opening a vault only counts them
+    fn load_archives(&mut self) {
+        self.selected.clear();
+        self.archives = None;
+        let Some(vault) = self.vault() else {
+            return;
+        };
+        match open_index().and_then(|index| IndexedArchives::new(index, &vault)) {
+            Ok(archives) => {
+                self.status = cached_inventory_age(&vault)
+                    .map(|x| describe_inventory_age(&x))
+                    .unwrap_or_default();
+                self.archives = Some(archives);
+            }
+            Err(reason) => self.status = reason.to_string(),
+        }
+    }
+
+    fn highlighted_archive(&mut self) -> Option<ArchiveItem> {
+        match self.archives.as_mut()?.highlighted_item() {
+            Ok(archive) => archive,
+            Err(reason) => {
+                self.status = reason.to_string();
+                None
+            }
+        }
+    }
+
+    async fn refresh(&mut self) {
+        self.load_archives();
+        self.jobs = Events::new(get_jobs().await.unwrap_or_default());
+    }
+
+    fn toggle_archive(&mut self) {
+        if let Some(archive) = self.highlighted_archive() {
+            self.selected.toggle(archive);
+        }
+    }
+
+    /// The selected archives, or the highlighted one when none are selected
+    fn target_archives(&mut self) -> Vec<ArchiveItem> {
+        if self.selected.is_empty() {
+            self.highlighted_archive().into_iter().collect()
+        } else {
+            self.selected.items().to_vec()
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let rows = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(65),
+                Constraint::Min(5),
+                Constraint::Length(1),
+            ])
+            .split(frame.size());
+        let columns = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(25), Constraint::Percentage(75)])
+            .split(rows[0]);
+
+        let focus = self.focus;
+        let border = self.theme.border;
+        let block = |title: String, pane: Pane| {
+            let style = if focus == pane {
+                border
+            } else {
+                Style::default()
+            };
+            Block::default()
+                .title(title)
+                .borders(Borders::ALL)
+                .border_style(style)
+        };
+        let list = |items: Vec<ListItem<'static>>, block: Block<'static>| {
+            List::new(items)
+                .block(block)
+                .highlight_style(Style::new().italic().bold())
+                .highlight_symbol("->")
+        };
+
+        let vaults: Vec<ListItem> = self
+            .vaults
+            .visible_items()
+            .map(|x| ListItem::new(x.clone()))
+            .collect();
+        frame.render_stateful_widget(
+            list(vaults, block(String::from("Vaults"), Pane::Vaults)),
+            columns[0],
+            self.vaults.state(),
+        );
+
+        // only the archives on screen are read from the index
+        let height = columns[1].height.saturating_sub(2) as usize;
+        let (page, mut window) = match self.archives.as_mut() {
+            Some(archives) => {
+                let (offset, window) = scroll_page(archives.state(), height);
+                match archives.page(offset, height) {
+                    Ok(page) => (page, window),
+                    Err(reason) => {
+                        self.status = reason.to_string();
+                        (vec![], window)
+                    }
+                }
+            }
+            None => (vec![], ListState::default()),
+        };
+        let archives: Vec<ListItem> = page
+            .iter()
+            .map(|x| {
+                if self.selected.contains(x) {
+                    ListItem::new(format!("{} *", x.label())).style(self.theme.selected)
+                } else {
+                    ListItem::new(x.label()).style(self.theme.text)
+                }
+            })
+            .collect();
+        frame.render_stateful_widget(
+            list(
+                archives,
+                block(
+                    format!(
+                        "Archives | {} selected, {}",
+                        self.selected.len(),
+                        human_size(self.selected.size().unwrap_or(0))
+                    ),
+                    Pane::Archives,
+                ),
+            ),
+            columns[1],
+            &mut window,
+        );
+
+        let jobs: Vec<ListItem> = self
+            .jobs
+            .visible_items()
+            .map(|x| ListItem::new(x.label()))
+            .collect();
+        frame.render_stateful_widget(
+            list(jobs, block(String::from("Local jobs"), Pane::Jobs)),
+            rows[1],
+            self.jobs.state(),
+        );
+
+        let status = match &self.prompt {
+            Some(Prompt::File(value)) => format!("file to upload: {}_", value),
+            Some(Prompt::Description { value, .. }) => format!("archive description: {}_", value),
+            None if !self.status.is_empty() => self.status.clone(),
+            None => [
+                (Command::NextPane, "pane"),
+                (Command::Toggle, "select |"),
+                (Command::Inventory, "inventory"),
+                (Command::Download, "download"),
+                (Command::Upload, "upload"),
+                (Command::Delete, "delete"),
+                (Command::CollectJobs, "collect jobs"),
+                (Command::Refresh, "refresh"),
+                (Command::Quit, "quit"),
+            ]
+            .map(|(command, name)| format!("{} {}", self.keys.label(command), name))
+            .join(" "),
+        };
+        frame.render_widget(Paragraph::new(status).style(self.theme.accent), rows[2]);
+    }
+
+    /// Returns the upload to run once both the file and the description have been typed in
+    fn handle_prompt(&mut self, prompt: Prompt, key: &KeyEvent) -> Option<Action> {
+        let (file, mut value) = match prompt {
+            Prompt::File(value) => (None, value),
+            Prompt::Description { file, value } => (Some(file), value),
+        };
+        match self
+            .keys
+            .command(key, &[Command::Cancel, Command::Confirm, Command::Erase])
+        {
+            Some(Command::Cancel) => return None,
+            Some(Command::Confirm) => match file {
+                None => {
+                    self.prompt = Some(Prompt::Description {
+                        file: value,
+                        value: String::new(),
+                    });
+                    return None;
+                }
+                Some(file) => {
+                    return self.vault().map(|vault| Action::Upload {
+                        vault,
+                        file,
+                        description: value,
+                    })
+                }
+            },
+            Some(Command::Erase) => {
+                value.pop();
+            }
+            // characters are typed into the prompt, even the ones bound to commands
+            _ => match key.code {
+                KeyCode::Char(x)
+                    if !key
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                {
+                    value.push(x)
+                }
+                _ => {}
+            },
+        }
+        self.prompt = Some(match file {
+            None => Prompt::File(value),
+            Some(file) => Prompt::Description { file, value },
+        });
+        None
+    }
+
+    fn handle_key(&mut self, key: &KeyEvent) -> Option<Action> {
+        self.status.clear();
+        let command = self.keys.command(
+            key,
+            &[
+                Command::NextPane,
+                Command::Down,
+                Command::Up,
+                Command::Toggle,
+                Command::Inventory,
+                Command::Download,
+                Command::Delete,
+                Command::Upload,
+                Command::CollectJobs,
+            ],
+        )?;
+        match command {
+            Command::NextPane => self.focus = self.focus.next(),
+            Command::Down => match self.focus {
+                Pane::Vaults => {
+                    self.vaults.next();
+                    self.load_archives();
+                }
+                Pane::Archives => {
+                    if let Some(archives) = self.archives.as_mut() {
+                        archives.next()
+                    }
+                }
+                Pane::Jobs => self.jobs.next(),
+            },
+            Command::Up => match self.focus {
+                Pane::Vaults => {
+                    self.vaults.previous();
+                    self.load_archives();
+                }
+                Pane::Archives => {
+                    if let Some(archives) = self.archives.as_mut() {
+                        archives.previous()
+                    }
+                }
+                Pane::Jobs => self.jobs.previous(),
+            },
+            Command::Toggle if self.focus == Pane::Archives => self.toggle_archive(),
+            Command::Inventory => return self.vault().map(Action::Inventory),
+            Command::Download => {
+                let archives = self.target_archives();
+                if !archives.is_empty() {
+                    return self.vault().map(|x| Action::Download(x, archives));
+                }
+            }
+            Command::Delete => {
+                let archives = self.target_archives();
+                if !archives.is_empty() {
+                    return self.vault().map(|x| Action::Delete(x, archives));
+                }
+            }
+            Command::Upload if self.vault().is_some() => {
+                self.prompt = Some(Prompt::File(String::new()))
+            }
+            Command::CollectJobs => return Some(Action::CollectJobs),
+            _ => {}
+        }
+        None
+    }
+}
+
+async fn perform(client: &Client, action: Action) -> Result<(), anyhow::Error> {
+    match action {
+        Action::Inventory(vault) => {
+            do_inventory(
+                client,
+                &Some(vault),
+                &InventoryFilters::default(),
+                &InventoryFormat::Json,
+                &true,
+                &false,
+            )
+            .await
+        }
+        Action::Download(vault, archives) => {
+            let (pending, failures) = initiate_retrievals(client, &vault, archives, ".").await?;
+            println!(
+                "{} retrieval jobs initiated - collect the archives with `p` once the jobs have completed, usually in 3 to 5 hours",
+                pending.len()
+            );
+            if failures > 0 {
+                return Err(anyhow!("{} retrievals failed to initiate", failures));
+            }
+            Ok(())
+        }
+        Action::Upload {
+            vault,
+            file,
+            description,
+        } => do_multipart_upload(client, &file, &vault, &description).await,
+        Action::Delete(vault, archives) => delete_archives(client, &vault, &archives, false).await,
+        Action::CollectJobs => {
+            resolve_all_pending(client, JobType::Inventory, ".").await?;
+            resolve_all_pending(client, JobType::Retrieval, ".").await?;
+            println!("{}", Colorize::green("collected completed jobs"));
+            Ok(())
+        }
+    }
+}
+
+/// Browse vaults, cached inventories and local jobs, and start operations on them without
+/// leaving the terminal UI
+pub async fn run(client: &Client) -> Result<(), anyhow::Error> {
+    let mut browser = Browser::new(client).await?;
+    let mut terminal = enter_terminal()?;
+    loop {
+        terminal.draw(|frame| browser.draw(frame))?;
+        if !event::poll(time::Duration::from_millis(50))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if is_interrupt(&key) {
+            abort();
+        }
+        let action = match browser.prompt.take() {
+            Some(prompt) => browser.handle_prompt(prompt, &key),
+            None => match browser
+                .keys
+                .command(&key, &[Command::Quit, Command::Refresh])
+            {
+                Some(Command::Quit) => break,
+                Some(_) => {
+                    browser.refresh().await;
+                    None
+                }
+                None => browser.handle_key(&key),
+            },
+        };
+        if let Some(action) = action {
+            drop(terminal);
+            if let Err(reason) = perform(client, action).await {
+                eprintln!("{}", reason);
+            }
+            eprintln!("press Enter to return to the browser");
+            std::io::stdin().read_line(&mut String::new())?;
+            terminal = enter_terminal()?;
+            browser.refresh().await;
+        }
+    }
+    Ok(())
+}
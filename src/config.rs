@@ -0,0 +1,62 @@
+use crate::keymap::{Command, KeySpec};
+use crate::shared::basmati_directory;
+use crate::theme::ThemeConfig;
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+
+fn default_inventory_max_age_days() -> i64 {
+    7
+}
+
+/// User settings read from `~/.basmati/config.json`, every setting is optional
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Config {
+    /// Cached inventories older than this are reported as stale, 0 turns the warning off
+    #[serde(default = "default_inventory_max_age_days")]
+    pub inventory_max_age_days: i64,
+    /// Keys of the TUI screens, replacing the default keys of the commands listed
+    #[serde(default)]
+    pub keys: HashMap<Command, Vec<KeySpec>>,
+    #[serde(default)]
+    pub theme: ThemeConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            inventory_max_age_days: default_inventory_max_age_days(),
+            keys: HashMap::new(),
+            theme: ThemeConfig::default(),
+        }
+    }
+}
+
+impl Config {
+    pub fn is_stale(&self, age_in_seconds: i64) -> bool {
+        self.inventory_max_age_days > 0 && age_in_seconds > self.inventory_max_age_days * 86400
+    }
+}
+
+pub fn config_path() -> String {
+    format!("{}/config.json", basmati_directory())
+}
+
+pub fn read_config() -> Result<Config, anyhow::Error> {
+    match fs::read_to_string(config_path()) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .map_err(|reason| anyhow!("Failed to parse {} - {}", config_path(), reason)),
+        Err(_) => Ok(Config::default()),
+    }
+}
+
+#[test]
+fn test_config_defaults() {
+    let config: Config = serde_json::from_str("{}").unwrap();
+    assert_eq!(config.inventory_max_age_days, 7);
+    assert!(config.is_stale(8 * 86400));
+    assert!(!config.is_stale(6 * 86400));
+    let config: Config = serde_json::from_str(r#"{"inventory_max_age_days": 0}"#).unwrap();
+    assert!(!config.is_stale(365 * 86400));
+}
@@ -1,26 +1,59 @@
-use crate::shared::get_archive_from_tui;
+use crate::index::{open_index, record_deletion};
+use crate::shared::{select_archives, ArchiveItem, ArchiveSelectors};
 use aws_sdk_glacier::Client;
 
-pub async fn do_deletion(client: &Client, vault_name: &String) -> Result<(), anyhow::Error> {
-    let archives = get_archive_from_tui(vault_name).await?;
-    match crate::shared::confirm(
-        String::from("Do you want to delete these archives"),
-        archives
-            .iter()
-            .map(|x| format!(" {} created on {}", x.archive_description, x.creation_date,))
-            .collect(),
-    ) {
+pub async fn do_deletion(
+    client: &Client,
+    vault_name: &String,
+    selectors: &ArchiveSelectors,
+    yes: bool,
+) -> Result<(), anyhow::Error> {
+    let archives = select_archives(client, vault_name, selectors).await?;
+    delete_archives(client, vault_name, &archives, yes).await
+}
+
+/// Delete archives once the user confirmed it, or right away with `yes`. Deleted archives are
+/// hidden from the cached inventory right away
+pub async fn delete_archives(
+    client: &Client,
+    vault_name: &String,
+    archives: &[ArchiveItem],
+    yes: bool,
+) -> Result<(), anyhow::Error> {
+    let confirmed = if yes {
+        Ok(true)
+    } else {
+        crate::shared::confirm(
+            String::from("Do you want to delete these archives"),
+            archives
+                .iter()
+                .map(|x| format!(" {} created on {}", x.archive_description, x.creation_date,))
+                .collect(),
+        )
+    };
+    match confirmed {
         Ok(true) => {
-            let mut jobs = archives.iter().map(|archive| {
+            let jobs = archives.iter().map(|archive| {
                 client
                     .delete_archive()
                     .account_id("-")
                     .vault_name(vault_name)
                     .archive_id(&archive.archive_id)
             });
-            while let Some(next_job) = jobs.next() {
+            let index = open_index()?;
+            for (next_job, archive) in jobs.zip(archives.iter()) {
                 match next_job.send().await {
-                    Ok(_) => println!("Successfully deleted"),
+                    Ok(_) => {
+                        println!("Successfully deleted {}", archive.archive_id);
+                        if let Err(reason) =
+                            record_deletion(&index, vault_name, &archive.archive_id)
+                        {
+                            eprintln!(
+                                "failed to remove the archive from the local index - {}",
+                                reason
+                            );
+                        }
+                    }
                     Err(reason) => {
                         println!("archive deletion failed! - {}", reason);
                     }
@@ -0,0 +1,149 @@
+use crate::delete_archive::delete_archives;
+use crate::index::{forget_vault, indexed_archive_count, indexed_inventory_date, open_index};
+use crate::inventory::{
+    initiate_inventory, inventory_directory, snapshot_directory, InventoryFilters, InventoryFormat,
+};
+use crate::shared::{confirm, get_jobs, human_size, read_inventory, JobType};
+use anyhow::anyhow;
+use aws_sdk_glacier::Client;
+use colored::Colorize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Delete a vault that Glacier considers empty and drop its cached inventory and index. The
+/// snapshots are kept as a record of what the vault held
+async fn delete_empty_vault(client: &Client, vault_name: &str) -> Result<(), anyhow::Error> {
+    client
+        .delete_vault()
+        .account_id("-")
+        .vault_name(vault_name)
+        .send()
+        .await
+        .map_err(|reason| {
+            anyhow!(
+                "Failed to delete vault {} - Glacier only deletes vaults that had no archives as of their last inventory and no writes since, use --empty to delete the archives first - {}",
+                vault_name,
+                reason
+            )
+        })?;
+    println!(
+        "The following vault has been deleted: {}",
+        vault_name.yellow()
+    );
+    forget_vault(&mut open_index()?, vault_name)?;
+    let local = inventory_directory(vault_name);
+    if !Path::new(&local).exists() {
+        return Ok(());
+    }
+    let snapshots = PathBuf::from(snapshot_directory(vault_name));
+    for entry in fs::read_dir(local)? {
+        let path = entry?.path();
+        if path == snapshots {
+            continue;
+        }
+        if path.is_dir() {
+            fs::remove_dir_all(path)?;
+        } else {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Delete every archive of the cached inventory, then the vault once an inventory taken after the
+/// deletions lists no archives. Deleted archives are recorded in the index, so running it again
+/// resumes where an interrupted run stopped and eventually deletes the vault
+async fn empty_and_delete_vault(
+    client: &Client,
+    vault_name: &str,
+    yes: bool,
+) -> Result<(), anyhow::Error> {
+    let index = open_index()?;
+    let archives = read_inventory(vault_name)?;
+    if !archives.is_empty() {
+        let size: i64 = archives.iter().map(|x| x.size).sum();
+        let already_deleted = indexed_archive_count(&index, vault_name)? - archives.len() as i64;
+        if already_deleted > 0 {
+            eprintln!(
+                "resuming - {} archives were deleted by an earlier run",
+                already_deleted
+            );
+        }
+        let confirmed = yes
+            || confirm(
+                format!("Do you want to empty and delete the vault {}", vault_name),
+                vec![
+                    format!(
+                        " {} archives, {} in total, are deleted first",
+                        archives.len(),
+                        human_size(size)
+                    ),
+                    String::from(" Deleted archives can not be recovered"),
+                ],
+            )?;
+        if !confirmed {
+            println!("exiting");
+            return Ok(());
+        }
+        delete_archives(client, &String::from(vault_name), &archives, true).await?;
+        let remaining = read_inventory(vault_name)?.len();
+        if remaining > 0 {
+            return Err(anyhow!(
+                "{} archives could not be deleted - run the command again to retry them",
+                remaining
+            ));
+        }
+    }
+
+    if indexed_inventory_date(&index, vault_name)?.is_some()
+        && indexed_archive_count(&index, vault_name)? == 0
+    {
+        return delete_empty_vault(client, vault_name).await;
+    }
+
+    let inventory_pending = get_jobs()
+        .await?
+        .iter()
+        .any(|x| x.vault == vault_name && x.job_type == JobType::Inventory);
+    if !inventory_pending {
+        let init_output = initiate_inventory(
+            client,
+            vault_name,
+            &InventoryFilters::default(),
+            InventoryFormat::Json,
+        )
+        .await?;
+        eprintln!(
+            "inventory job {} recorded",
+            init_output.job_id().unwrap_or_default().yellow()
+        );
+    }
+    println!(
+        "Every archive has been deleted. Glacier refreshes inventories about once a day, collect the inventory with `basmati inventory --pending` and run `basmati delete-vault -v {} --empty` again to delete the vault once the inventory lists no archives",
+        vault_name
+    );
+    Ok(())
+}
+
+pub async fn do_delete_vault(
+    client: &Client,
+    vault_name: &str,
+    empty: bool,
+    yes: bool,
+) -> Result<(), anyhow::Error> {
+    if empty {
+        return empty_and_delete_vault(client, vault_name, yes).await;
+    }
+    let confirmed = yes
+        || confirm(
+            format!("Do you want to delete the vault {}", vault_name),
+            vec![String::from(
+                " The vault has to be empty as of its last inventory",
+            )],
+        )?;
+    if !confirmed {
+        println!("exiting");
+        return Ok(());
+    }
+    delete_empty_vault(client, vault_name).await
+}
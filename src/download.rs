@@ -1,22 +1,23 @@
 use crate::inventory::resolve_all_pending;
 
 use crate::shared::{
-    describe_job_loop, get_archive_from_tui, get_job_output, save_job_output, JobType, Status,
+    describe_job_loop, description_output_path, download_when_ready, get_job_output, output_writer,
+    save_job_output, select_archives, ArchiveItem, ArchiveSelectors, JobType, PendingJob, Status,
 };
 use anyhow::{anyhow, Result};
 use aws_sdk_glacier::types::JobParameters;
 use aws_sdk_glacier::Client;
-use std::fs;
+use colored::Colorize;
+use std::collections::HashSet;
+use std::path::PathBuf;
 
-async fn download_archive_by_id(
+async fn initiate_retrieval(
     client: &Client,
     vault_name: &String,
-    archive_id: String,
-    output_as: Option<String>,
-) -> Result<(), anyhow::Error> {
-    println!("download_archive_by_id gonna init, {}", archive_id);
-
-    let init = client
+    archive_id: &str,
+    output_path: Option<&str>,
+) -> Result<String, anyhow::Error> {
+    let init_output = client
         .initiate_job()
         .account_id("-")
         .vault_name(vault_name)
@@ -27,58 +28,141 @@ async fn download_archive_by_id(
                 .build(),
         )
         .send()
-        .await;
+        .await?;
+    let job_id = String::from(init_output.job_id().unwrap());
+    save_job_output(
+        init_output,
+        JobType::Retrieval,
+        output_path.map(String::from),
+    )
+    .await?;
+    Ok(job_id)
+}
 
-    match init {
-        Ok(init_ouput) => {
-            println!("initiated retrieval job successfuly...");
+/// Initiate the retrieval of several archives, each one is recorded to be written into
+/// `output_dir` under a name of its own. Returns the jobs that were initiated and how many
+/// archives failed to initiate
+pub async fn initiate_retrievals(
+    client: &Client,
+    vault_name: &String,
+    archives: Vec<ArchiveItem>,
+    output_dir: &str,
+) -> Result<(Vec<PendingJob>, usize), anyhow::Error> {
+    let mut reserved = HashSet::new();
+    let mut pending = vec![];
+    let mut failures = 0;
+    for archive in archives {
+        let path = description_output_path(
+            &archive.archive_description,
+            &archive.archive_id,
+            output_dir,
+            &reserved,
+        )?;
+        reserved.insert(PathBuf::from(&path));
+        match initiate_retrieval(client, vault_name, &archive.archive_id, Some(&path)).await {
+            Ok(job_id) => {
+                eprintln!(
+                    "{}: {}",
+                    "initiated retrieval".green(),
+                    archive.archive_description
+                );
+                pending.push(PendingJob {
+                    vault: vault_name.clone(),
+                    job_id,
+                    output_path: path,
+                });
+            }
+            Err(reason) => {
+                eprintln!(
+                    "initiation failed for {}: {}",
+                    archive.archive_description, reason
+                );
+                failures += 1;
+            }
+        }
+    }
+    Ok((pending, failures))
+}
 
-            save_job_output(init_ouput.clone(), JobType::Retrieval)
-                .await
-                .expect("Was not able to save metadata");
+/// Retrieve several archives at once: every job is initiated up front, the jobs are polled
+/// together and each archive is downloaded into `output_dir` as soon as its job completes, with
+/// at most `concurrency` downloads running at the same time
+async fn download_archives(
+    client: &Client,
+    vault_name: &String,
+    archives: Vec<ArchiveItem>,
+    output_dir: &str,
+    concurrency: usize,
+) -> Result<(), anyhow::Error> {
+    let (pending, failures) = initiate_retrievals(client, vault_name, archives, output_dir).await?;
+    // the archives that were initiated are still downloaded
+    download_when_ready(client, pending, concurrency).await?;
+    if failures > 0 {
+        return Err(anyhow!("{} retrievals failed to initiate", failures));
+    }
+    Ok(())
+}
+
+async fn download_archive_by_id(
+    client: &Client,
+    vault_name: &String,
+    archive: &ArchiveItem,
+    output_as: Option<String>,
+    output_dir: &str,
+) -> Result<(), anyhow::Error> {
+    eprintln!("download_archive_by_id gonna init, {}", archive.archive_id);
+
+    let filename = match output_as {
+        Some(value) if value == "-" => value,
+        Some(value) => std::path::absolute(value)?.display().to_string(),
+        None => description_output_path(
+            &archive.archive_description,
+            &archive.archive_id,
+            output_dir,
+            &HashSet::new(),
+        )?,
+    };
+
+    // there is no stdout to write to when finishing the job later with --pending
+    let recorded_path = (filename != "-").then_some(filename.as_str());
+    match initiate_retrieval(client, vault_name, &archive.archive_id, recorded_path).await {
+        Ok(job_id) => {
+            eprintln!("initiated retrieval job successfuly...");
 
             let describe_builder = client
                 .describe_job()
                 .account_id("-")
                 .vault_name(vault_name)
-                .job_id(init_ouput.job_id().unwrap());
+                .job_id(&job_id);
 
             if let Ok(mut describe_output) = describe_job_loop(describe_builder.clone()).await {
-                println!(
+                eprintln!(
                     "job {} is ready, attempting to download",
                     describe_output.job_id.as_mut().unwrap()
                 );
 
-                let filename = match output_as {
-                    Some(value) => value,
-                    None => init_ouput.job_id().unwrap().to_owned(),
-                };
-                let file = fs::File::create(filename).expect("failed to create user defined file");
+                let file = output_writer(&filename).await?;
                 let builder = client
                     .get_job_output()
                     .account_id("-")
                     .vault_name(vault_name)
                     .job_id(describe_output.job_id().unwrap());
 
+                // a truncated archive must not look like a complete one to whoever reads the output
                 match get_job_output(builder, file).await {
                     Ok(Status::Done) => {
-                        println!("Writing complete!");
-                        return Ok(());
-                    }
-                    Err(err) => {
-                        println!("failed to get archive output, {:?}", err);
-                        return Ok(());
-                    }
-                    _ => {
-                        return Ok(());
+                        eprintln!("Writing complete: {}", filename);
+                        Ok(())
                     }
+                    Err(err) => Err(anyhow!("failed to get archive output - {}", err)),
+                    _ => Err(anyhow!("failed to get archive output")),
                 }
             } else {
                 Err(anyhow!(format!("retrieval describe job failed")))
             }
         }
         Err(reason) => {
-            println!("initation failed: check your that your AWS secrets are set");
+            eprintln!("initation failed: check your that your AWS secrets are set");
             Err(anyhow!(reason))
         }
     }
@@ -88,18 +172,19 @@ pub async fn do_download(
     client: &Client,
     vault_name: &Option<String>,
     output_as: &Option<String>,
+    output_dir: &str,
     pending: &bool,
+    concurrency: &usize,
+    selectors: &ArchiveSelectors,
 ) -> Result<(), anyhow::Error> {
     if *pending {
-        match resolve_all_pending(client, crate::shared::JobType::Retrieval).await {
+        match resolve_all_pending(client, crate::shared::JobType::Retrieval, output_dir).await {
             Ok(Status::Done) => {
-                println!("Finished processing pending archive retrievals");
-                return Ok(());
-            }
-            _ => {
-                println!("Something went wrong");
+                eprintln!("Finished processing pending archive retrievals");
                 return Ok(());
             }
+            Ok(_) => return Err(anyhow!("Downloading pending archive retrievals failed")),
+            Err(reason) => return Err(reason),
         }
     }
     let vault_name = String::from(
@@ -108,35 +193,25 @@ pub async fn do_download(
             .expect("Expected vault_name to be defined"),
     );
 
-    match get_archive_from_tui(&vault_name).await {
-        Ok(archives) => {
-            if archives.len() > 1 {
-                return Err(anyhow!(
-                    "You must select a single archive and no more than one archive to download"
-                ));
-            }
-
-            let archive = archives.first().unwrap();
-            match download_archive_by_id(
-                &client,
-                &vault_name,
-                archive.archive_id.clone(),
-                output_as.clone().to_owned(),
-            )
-            .await
-            {
-                Ok(_success_op) => {
-                    println!("Operation completed successfully")
-                }
-                Err(reason) => {
-                    println!("{:?}", reason)
-                }
-            }
-        }
-        Err(reason) => {
-            println!("{:?}", reason)
+    let archives = select_archives(client, &vault_name, selectors).await?;
+    if archives.len() > 1 {
+        if output_as.is_some() {
+            return Err(anyhow!(
+                "--output-as names a single file - use --output-dir when downloading several archives"
+            ));
         }
+        return download_archives(client, &vault_name, archives, output_dir, *concurrency).await;
     }
 
+    let archive = archives.first().unwrap();
+    download_archive_by_id(
+        client,
+        &vault_name,
+        archive,
+        output_as.clone().to_owned(),
+        output_dir,
+    )
+    .await?;
+    eprintln!("Operation completed successfully");
     Ok(())
 }
@@ -0,0 +1,631 @@
+use crate::ls::{ArchiveFilters, SortKey};
+#[cfg(test)]
+use crate::shared::test_archive;
+use crate::shared::{basmati_directory, next_sort, ArchiveItem, Cursor, Listing, Vault};
+use anyhow::anyhow;
+use chrono::{SecondsFormat, Utc};
+use ratatui::widgets::ListState;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use std::fs;
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS archives (
+    vault TEXT NOT NULL,
+    archive_id TEXT NOT NULL,
+    description TEXT NOT NULL,
+    creation_date TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    sha256_tree_hash TEXT NOT NULL,
+    -- the inventory that last listed the archive, NULL for uploads no inventory has confirmed yet
+    inventory_date TEXT,
+    PRIMARY KEY (vault, archive_id)
+);
+CREATE INDEX IF NOT EXISTS archives_by_date ON archives (vault, creation_date);
+CREATE INDEX IF NOT EXISTS archives_by_size ON archives (vault, size);
+-- archives deleted with basmati that the last inventory still lists
+CREATE TABLE IF NOT EXISTS deletions (
+    vault TEXT NOT NULL,
+    archive_id TEXT NOT NULL,
+    deletion_date TEXT NOT NULL,
+    PRIMARY KEY (vault, archive_id)
+);
+CREATE TABLE IF NOT EXISTS inventories (
+    vault TEXT PRIMARY KEY,
+    vault_arn TEXT NOT NULL,
+    inventory_date TEXT NOT NULL
+);
+";
+
+fn index_path() -> String {
+    format!("{}/index.db", basmati_directory())
+}
+
+fn init_schema(conn: &Connection) -> Result<(), anyhow::Error> {
+    conn.execute_batch(SCHEMA)?;
+    Ok(())
+}
+
+/// Open the local index of all vault inventories, creating it on first use
+pub fn open_index() -> Result<Connection, anyhow::Error> {
+    fs::create_dir_all(basmati_directory())?;
+    let conn = Connection::open(index_path())?;
+    conn.busy_timeout(std::time::Duration::from_secs(5))?;
+    init_schema(&conn)?;
+    Ok(conn)
+}
+
+fn archive_from_row(row: &Row) -> rusqlite::Result<ArchiveItem> {
+    Ok(ArchiveItem {
+        archive_id: row.get(0)?,
+        archive_description: row.get(1)?,
+        creation_date: row.get(2)?,
+        size: row.get(3)?,
+        sha256_tree_hash: row.get(4)?,
+        pending_upload: row.get(5)?,
+    })
+}
+
+/// The date of the last inventory of a vault in the index
+pub fn indexed_inventory_date(
+    conn: &Connection,
+    vault_name: &str,
+) -> Result<Option<String>, anyhow::Error> {
+    Ok(conn
+        .query_row(
+            "SELECT inventory_date FROM inventories WHERE vault = ?1",
+            params![vault_name],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+/// Bring the index of a vault up to date with an inventory. Archives are upserted and only the
+/// ones the inventory no longer lists are removed, uploads are kept until an inventory lists them
+/// and deletions until an inventory no longer lists them
+pub fn index_inventory(
+    conn: &mut Connection,
+    vault_name: &str,
+    inventory: &Vault,
+) -> Result<(), anyhow::Error> {
+    if indexed_inventory_date(conn, vault_name)?.as_deref() == Some(&inventory.inventory_date) {
+        return Ok(());
+    }
+    let tx = conn.transaction()?;
+    {
+        let mut upsert = tx.prepare(
+            "INSERT INTO archives
+                (vault, archive_id, description, creation_date, size, sha256_tree_hash, inventory_date)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (vault, archive_id) DO UPDATE SET
+                description = excluded.description,
+                creation_date = excluded.creation_date,
+                size = excluded.size,
+                sha256_tree_hash = excluded.sha256_tree_hash,
+                inventory_date = excluded.inventory_date",
+        )?;
+        for archive in inventory.archive_list.iter() {
+            upsert.execute(params![
+                vault_name,
+                archive.archive_id,
+                archive.archive_description,
+                archive.creation_date,
+                archive.size,
+                archive.sha256_tree_hash,
+                inventory.inventory_date
+            ])?;
+        }
+    }
+    tx.execute(
+        "DELETE FROM archives
+         WHERE vault = ?1 AND inventory_date IS NOT NULL AND inventory_date != ?2",
+        params![vault_name, inventory.inventory_date],
+    )?;
+    tx.execute(
+        "DELETE FROM deletions
+         WHERE vault = ?1 AND archive_id NOT IN (SELECT archive_id FROM archives WHERE vault = ?1)",
+        params![vault_name],
+    )?;
+    tx.execute(
+        "INSERT OR REPLACE INTO inventories (vault, vault_arn, inventory_date) VALUES (?1, ?2, ?3)",
+        params![vault_name, inventory.vault_arn, inventory.inventory_date],
+    )?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Add an archive uploaded by basmati to the index ahead of the next inventory
+pub fn record_upload(
+    conn: &Connection,
+    vault_name: &str,
+    archive: &ArchiveItem,
+) -> Result<(), anyhow::Error> {
+    conn.execute(
+        "INSERT INTO archives
+            (vault, archive_id, description, creation_date, size, sha256_tree_hash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT (vault, archive_id) DO NOTHING",
+        params![
+            vault_name,
+            archive.archive_id,
+            archive.archive_description,
+            archive.creation_date,
+            archive.size,
+            archive.sha256_tree_hash
+        ],
+    )?;
+    Ok(())
+}
+
+/// Hide a deleted archive until the next inventory confirms the deletion, uploads that no
+/// inventory has listed yet are dropped right away
+pub fn record_deletion(
+    conn: &Connection,
+    vault_name: &str,
+    archive_id: &str,
+) -> Result<(), anyhow::Error> {
+    let removed = conn.execute(
+        "DELETE FROM archives
+         WHERE vault = ?1 AND archive_id = ?2 AND inventory_date IS NULL",
+        params![vault_name, archive_id],
+    )?;
+    if removed == 0 {
+        conn.execute(
+            "INSERT OR IGNORE INTO deletions (vault, archive_id, deletion_date) VALUES (?1, ?2, ?3)",
+            params![
+                vault_name,
+                archive_id,
+                Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+/// How many archives of a vault are hidden because they were deleted since the last inventory
+pub fn pending_deletions(conn: &Connection, vault_name: &str) -> Result<i64, anyhow::Error> {
+    Ok(conn.query_row(
+        "SELECT COUNT(*) FROM deletions WHERE vault = ?1",
+        params![vault_name],
+        |row| row.get(0),
+    )?)
+}
+
+/// How many archives the index holds for a vault, counting the ones hidden because they were
+/// deleted since the last inventory
+pub fn indexed_archive_count(conn: &Connection, vault_name: &str) -> Result<i64, anyhow::Error> {
+    Ok(conn.query_row(
+        "SELECT COUNT(*) FROM archives WHERE vault = ?1",
+        params![vault_name],
+        |row| row.get(0),
+    )?)
+}
+
+/// How many archives of a vault were uploaded with basmati since the last inventory
+pub fn pending_uploads(conn: &Connection, vault_name: &str) -> Result<i64, anyhow::Error> {
+    Ok(conn.query_row(
+        "SELECT COUNT(*) FROM archives WHERE vault = ?1 AND inventory_date IS NULL",
+        params![vault_name],
+        |row| row.get(0),
+    )?)
+}
+
+/// Drop everything the index knows about a deleted vault
+pub fn forget_vault(conn: &mut Connection, vault_name: &str) -> Result<(), anyhow::Error> {
+    let tx = conn.transaction()?;
+    for table in ["archives", "deletions", "inventories"] {
+        tx.execute(
+            &format!("DELETE FROM {} WHERE vault = ?1", table),
+            params![vault_name],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Inventories pulled down before the index existed are imported the first time a vault is read
+fn import_inventory_file(conn: &mut Connection, vault_name: &str) -> Result<(), anyhow::Error> {
+    let path = format!(
+        "{}/vault/{}/inventory.json",
+        basmati_directory(),
+        vault_name
+    );
+    if indexed_inventory_date(conn, vault_name)?.is_some() || !Path::new(&path).exists() {
+        return Ok(());
+    }
+    let inventory: Vault = serde_json::from_slice(&fs::read(path)?)?;
+    index_inventory(conn, vault_name, &inventory)
+}
+
+pub fn find_archive(
+    conn: &mut Connection,
+    vault_name: &str,
+    archive_id: &str,
+) -> Result<Option<ArchiveItem>, anyhow::Error> {
+    import_inventory_file(conn, vault_name)?;
+    Ok(conn
+        .query_row(
+            "SELECT archive_id, description, creation_date, size, sha256_tree_hash,
+                inventory_date IS NULL
+             FROM archives WHERE vault = ?1 AND archive_id = ?2",
+            params![vault_name, archive_id],
+            archive_from_row,
+        )
+        .optional()?)
+}
+
+fn sort_column(key: SortKey) -> &'static str {
+    match key {
+        SortKey::Date => "creation_date",
+        SortKey::Size => "size",
+        SortKey::Description => "description COLLATE NOCASE",
+    }
+}
+
+/// Date and size filters and the ordering are applied by SQLite, the description pattern is
+/// matched on the rows it returns
+pub fn query_archives(
+    conn: &mut Connection,
+    vault_name: &str,
+    filters: &ArchiveFilters,
+    sort: SortKey,
+    reverse: bool,
+) -> Result<Vec<ArchiveItem>, anyhow::Error> {
+    import_inventory_file(conn, vault_name)?;
+    let order = sort_column(sort);
+    let direction = if reverse { "DESC" } else { "ASC" };
+    let mut statement = conn.prepare(&format!(
+        "SELECT archive_id, description, creation_date, size, sha256_tree_hash,
+            inventory_date IS NULL
+         FROM archives
+         WHERE vault = ?1
+            AND archive_id NOT IN (SELECT archive_id FROM deletions WHERE vault = ?1)
+            AND (?2 IS NULL OR creation_date >= ?2)
+            AND (?3 IS NULL OR creation_date < ?3)
+            AND (?4 IS NULL OR size >= ?4)
+            AND (?5 IS NULL OR size <= ?5)
+         ORDER BY {} {}, archive_id",
+        order, direction
+    ))?;
+    let date = |x: &chrono::DateTime<chrono::Utc>| x.to_rfc3339_opts(SecondsFormat::Secs, true);
+    let rows = statement.query_map(
+        params![
+            vault_name,
+            filters.created_after.as_ref().map(date),
+            filters.created_before.as_ref().map(date),
+            filters.min_size,
+            filters.max_size
+        ],
+        archive_from_row,
+    )?;
+    let mut archives = vec![];
+    for archive in rows {
+        let archive = archive?;
+        if filters
+            .description
+            .as_ref()
+            .is_none_or(|x| x.is_match(&archive.archive_description))
+        {
+            archives.push(archive);
+        }
+    }
+    if archives.is_empty() && indexed_inventory_date(conn, vault_name)?.is_none() {
+        return Err(anyhow!(
+            "Failed to read the inventory - have you pulled down the inventory of the vault yet?"
+        ));
+    }
+    Ok(archives)
+}
+
+/// The archives of a vault as the picker shows them, searched, ordered and read a page at a time
+/// by SQLite
+pub struct IndexedArchives {
+    conn: Connection,
+    vault_name: String,
+    /// `LIKE` patterns of the terms of the search
+    terms: Vec<String>,
+    sort: Option<(SortKey, bool)>,
+    total: usize,
+    matching: usize,
+    state: ListState,
+    /// Offset and rows of the last page read, the picker draws the same page over and over
+    cached: Option<(usize, Vec<ArchiveItem>)>,
+}
+
+/// `LIKE` pattern matching the characters of a search term in order, the way `fuzzy_match` does for
+/// lists held in memory. SQLite only folds the case of ASCII letters
+fn like_pattern(term: &str) -> String {
+    let mut pattern = String::from("%");
+    for x in term.chars() {
+        if matches!(x, '%' | '_' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(x);
+        pattern.push('%');
+    }
+    pattern
+}
+
+impl IndexedArchives {
+    pub fn new(mut conn: Connection, vault_name: &str) -> Result<IndexedArchives, anyhow::Error> {
+        import_inventory_file(&mut conn, vault_name)?;
+        let mut archives = IndexedArchives {
+            conn,
+            vault_name: String::from(vault_name),
+            terms: vec![],
+            sort: None,
+            total: 0,
+            matching: 0,
+            state: ListState::default().with_selected(Some(0)),
+            cached: None,
+        };
+        archives.total = archives.count("")?;
+        archives.matching = archives.total;
+        if archives.total == 0 && indexed_inventory_date(&archives.conn, vault_name)?.is_none() {
+            return Err(anyhow!(
+                "Failed to read the inventory - have you pulled down the inventory of the vault yet?"
+            ));
+        }
+        Ok(archives)
+    }
+
+    /// The vault, the search terms and then `extra`, numbered the way `filter_clause` expects
+    fn parameters(&self, extra: Vec<Value>) -> Vec<Value> {
+        std::iter::once(Value::from(self.vault_name.clone()))
+            .chain(self.terms.iter().cloned().map(Value::from))
+            .chain(extra)
+            .collect()
+    }
+
+    fn filter_clause(&self) -> String {
+        let mut clause = String::from(
+            "vault = ?1 AND archive_id NOT IN (SELECT archive_id FROM deletions WHERE vault = ?1)",
+        );
+        for i in 0..self.terms.len() {
+            clause.push_str(&format!(
+                " AND description || ' ' || archive_id LIKE ?{} ESCAPE '\\'",
+                i + 2
+            ));
+        }
+        clause
+    }
+
+    /// The column and the direction of the order, archives stay in date order until sorted
+    fn order(&self) -> (&'static str, &'static str) {
+        let (key, reverse) = self.sort.unwrap_or((SortKey::Date, false));
+        (sort_column(key), if reverse { "DESC" } else { "ASC" })
+    }
+
+    fn count(&self, extra_clause: &str) -> Result<usize, anyhow::Error> {
+        let count: i64 = self.conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM archives WHERE {}{}",
+                self.filter_clause(),
+                extra_clause
+            ),
+            params_from_iter(self.parameters(vec![])),
+            |row| row.get(0),
+        )?;
+        Ok(count as usize)
+    }
+
+    /// Where an archive is shown with the current search and order, `None` when it does not match
+    fn position(&self, archive: &ArchiveItem) -> Result<Option<usize>, anyhow::Error> {
+        let (column, direction) = self.order();
+        let next = self.terms.len() + 2;
+        let parameters =
+            |key: Value| self.parameters(vec![key, Value::from(archive.archive_id.clone())]);
+        let matches: i64 = self.conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM archives WHERE {} AND archive_id = ?{}",
+                self.filter_clause(),
+                next + 1
+            ),
+            params_from_iter(parameters(Value::Null)),
+            |row| row.get(0),
+        )?;
+        if matches == 0 {
+            return Ok(None);
+        }
+        let key = match self.sort.map_or(SortKey::Date, |x| x.0) {
+            SortKey::Date => Value::from(archive.creation_date.clone()),
+            SortKey::Size => Value::from(archive.size),
+            SortKey::Description => Value::from(archive.archive_description.clone()),
+        };
+        let before = if direction == "DESC" { ">" } else { "<" };
+        let position: i64 = self.conn.query_row(
+            &format!(
+                "SELECT COUNT(*) FROM archives WHERE {} AND ({} {} ?{} OR ({} = ?{} AND archive_id < ?{}))",
+                self.filter_clause(),
+                column,
+                before,
+                next,
+                column,
+                next,
+                next + 1
+            ),
+            params_from_iter(parameters(key)),
+            |row| row.get(0),
+        )?;
+        Ok(Some(position as usize))
+    }
+
+    /// Re-run the search after the terms or the order changed, keeping the highlighted archive
+    /// highlighted when it still matches
+    fn refresh(&mut self, highlighted: Option<ArchiveItem>) -> Result<(), anyhow::Error> {
+        self.cached = None;
+        self.matching = self.count("")?;
+        let position = match highlighted {
+            Some(archive) => self.position(&archive)?,
+            None => None,
+        };
+        self.state.select(Some(position.unwrap_or(0)));
+        Ok(())
+    }
+}
+
+impl Cursor for IndexedArchives {
+    fn state(&mut self) -> &mut ListState {
+        &mut self.state
+    }
+
+    fn len(&self) -> usize {
+        self.matching
+    }
+}
+
+impl Listing<ArchiveItem> for IndexedArchives {
+    fn total(&self) -> usize {
+        self.total
+    }
+
+    fn page(&mut self, offset: usize, limit: usize) -> Result<Vec<ArchiveItem>, anyhow::Error> {
+        if let Some((start, rows)) = &self.cached {
+            if *start <= offset && offset + limit <= start + rows.len() {
+                return Ok(rows[offset - start..offset - start + limit].to_vec());
+            }
+        }
+        let (column, direction) = self.order();
+        let mut statement = self.conn.prepare(&format!(
+            "SELECT archive_id, description, creation_date, size, sha256_tree_hash,
+                inventory_date IS NULL
+             FROM archives
+             WHERE {}
+             ORDER BY {} {}, archive_id
+             LIMIT {} OFFSET {}",
+            self.filter_clause(),
+            column,
+            direction,
+            limit,
+            offset
+        ))?;
+        let rows = statement
+            .query_map(params_from_iter(self.parameters(vec![])), archive_from_row)?
+            .collect::<Result<Vec<_>, _>>()?;
+        drop(statement);
+        self.cached = Some((offset, rows.clone()));
+        Ok(rows)
+    }
+
+    fn search(&mut self, query: &str) -> Result<(), anyhow::Error> {
+        let highlighted = self.highlighted_item()?;
+        self.terms = query.split_whitespace().map(like_pattern).collect();
+        self.refresh(highlighted)
+    }
+
+    fn sort(&mut self, key: SortKey) -> Result<(), anyhow::Error> {
+        let highlighted = self.highlighted_item()?;
+        self.sort = next_sort(self.sort, key);
+        self.refresh(highlighted)
+    }
+
+    fn sorted_by(&self) -> Option<(SortKey, bool)> {
+        self.sort
+    }
+}
+
+#[test]
+fn test_index_inventory_reconciles_uploads_and_deletions() {
+    let archive = |id: &str, size: i64| {
+        test_archive(id, &format!("archive {}", id), "2024-01-01T00:00:00Z", size)
+    };
+    let inventory = |date: &str, archives: Vec<ArchiveItem>| Vault {
+        vault_arn: String::from("arn"),
+        inventory_date: String::from(date),
+        archive_list: archives,
+    };
+    let mut conn = Connection::open_in_memory().unwrap();
+    init_schema(&conn).unwrap();
+    let ids = |conn: &mut Connection| -> Vec<String> {
+        query_archives(conn, "x", &ArchiveFilters::default(), SortKey::Size, false)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.archive_id)
+            .collect()
+    };
+
+    index_inventory(
+        &mut conn,
+        "x",
+        &inventory(
+            "2024-02-01T00:00:00Z",
+            vec![archive("a", 1), archive("b", 2)],
+        ),
+    )
+    .unwrap();
+    record_upload(&conn, "x", &archive("c", 3)).unwrap();
+    record_upload(&conn, "x", &archive("d", 4)).unwrap();
+    record_deletion(&conn, "x", "a").unwrap();
+    record_deletion(&conn, "x", "d").unwrap();
+    assert_eq!(ids(&mut conn), vec!["b", "c"]);
+    assert_eq!(pending_deletions(&conn, "x").unwrap(), 1);
+    assert_eq!(pending_uploads(&conn, "x").unwrap(), 1);
+
+    index_inventory(
+        &mut conn,
+        "x",
+        &inventory("2024-03-01T00:00:00Z", vec![archive("b", 5)]),
+    )
+    .unwrap();
+    assert_eq!(ids(&mut conn), vec!["c", "b"]);
+    assert_eq!(pending_deletions(&conn, "x").unwrap(), 0);
+    assert_eq!(pending_uploads(&conn, "x").unwrap(), 1);
+
+    forget_vault(&mut conn, "x").unwrap();
+    assert_eq!(indexed_archive_count(&conn, "x").unwrap(), 0);
+    assert!(indexed_inventory_date(&conn, "x").unwrap().is_none());
+}
+
+#[test]
+fn test_indexed_archives_search_sort_and_page() {
+    let archive = |id: &str, description: &str, size: i64| {
+        test_archive(
+            id,
+            description,
+            &format!("2024-01-0{}T00:00:00Z", size),
+            size,
+        )
+    };
+    let mut conn = Connection::open_in_memory().unwrap();
+    init_schema(&conn).unwrap();
+    let inventory = Vault {
+        vault_arn: String::from("arn"),
+        inventory_date: String::from("2024-02-01T00:00:00Z"),
+        archive_list: vec![
+            archive("a", "Photos 2023", 2),
+            archive("b", "photos 2024", 3),
+            archive("c", "music 100%", 1),
+        ],
+    };
+    index_inventory(&mut conn, "x", &inventory).unwrap();
+    let mut archives = IndexedArchives::new(conn, "x").unwrap();
+    let ids = |archives: &mut IndexedArchives| -> Vec<String> {
+        archives
+            .page(0, 10)
+            .unwrap()
+            .into_iter()
+            .map(|x| x.archive_id)
+            .collect()
+    };
+    assert_eq!(ids(&mut archives), vec!["c", "a", "b"]);
+
+    archives.search("pho").unwrap();
+    assert_eq!((archives.len(), archives.total()), (2, 3));
+    archives.last();
+    archives.sort(SortKey::Size).unwrap();
+    archives.sort(SortKey::Size).unwrap();
+    assert_eq!(ids(&mut archives), vec!["b", "a"]);
+    assert_eq!(
+        archives.highlighted_item().unwrap().map(|x| x.archive_id),
+        Some(String::from("b"))
+    );
+    assert_eq!(archives.page(1, 1).unwrap()[0].archive_id, "a");
+
+    archives.search("ph 23").unwrap();
+    assert_eq!(ids(&mut archives), vec!["a"]);
+    archives.search("0%").unwrap();
+    assert_eq!(ids(&mut archives), vec!["c"]);
+    archives.search("_").unwrap();
+    assert!(archives.is_empty());
+}
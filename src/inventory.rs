@@ -1,53 +1,411 @@
 use anyhow::anyhow;
-use aws_sdk_glacier::types::JobParameters;
+use aws_sdk_glacier::operation::initiate_job::builders::InitiateJobFluentBuilder;
+use aws_sdk_glacier::operation::initiate_job::InitiateJobOutput;
+use aws_sdk_glacier::types::{InventoryRetrievalJobInput, JobParameters};
 use aws_sdk_glacier::Client;
+use clap::ValueEnum;
+use colored::Colorize;
+use std::collections::HashMap;
 use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 
+use crate::config::read_config;
+use crate::index::{index_inventory, indexed_inventory_date, open_index};
+use crate::list_vaults::list_all_vaults;
+#[cfg(test)]
+use crate::shared::test_archive;
 use crate::shared::{
-    basmati_directory, delete_expired_jobs_from_local, delete_job_from_local, describe_job_loop,
-    describe_job_output, get_job_output, get_jobs, save_job_output, JobType, Status,
+    basmati_directory, confirm, delete_expired_jobs_from_local, delete_job_from_local,
+    describe_job_loop, describe_job_output, format_duration, get_job_output, get_jobs,
+    output_writer, partition_completed, retrieval_output_path, save_job_output, seconds_since,
+    sleep_unless_done, ArchiveItem, InFlight, InitiatedJob, JobType, Status, Vault,
 };
 
+/// Narrow down an inventory job, see `InventoryRetrievalJobInput`
+/// Printed after initiating inventory jobs without waiting for them
+const COLLECT_LATER: &str =
+    "run `basmati inventory --pending` once completed, usually in 4 to 6 hours";
+
+#[derive(Debug, Default, Clone)]
+pub struct InventoryFilters {
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+    pub limit: Option<u32>,
+    pub marker: Option<String>,
+}
+
+/// The formats Glacier can return an inventory in
+#[derive(Debug, Default, Clone, Copy, PartialEq, ValueEnum)]
+pub enum InventoryFormat {
+    #[default]
+    Json,
+    Csv,
+}
+
+impl InventoryFormat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            InventoryFormat::Json => "JSON",
+            InventoryFormat::Csv => "CSV",
+        }
+    }
+
+    fn from_glacier(format: Option<&str>) -> InventoryFormat {
+        match format {
+            Some("CSV") => InventoryFormat::Csv,
+            _ => InventoryFormat::Json,
+        }
+    }
+}
+
+/// Glacier's CSV inventories only hold the archive list, the vault and the date of the inventory
+/// are taken from the job instead
+pub fn parse_csv_inventory(
+    bytes: &[u8],
+    vault_arn: &str,
+    inventory_date: &str,
+) -> Result<Vault, anyhow::Error> {
+    let archive_list = csv::Reader::from_reader(bytes)
+        .deserialize()
+        .collect::<Result<Vec<ArchiveItem>, csv::Error>>()?;
+    Ok(Vault {
+        vault_arn: String::from(vault_arn),
+        inventory_date: String::from(inventory_date),
+        archive_list,
+    })
+}
+
+#[test]
+fn test_parse_csv_inventory() {
+    let bytes = b"ArchiveId,ArchiveDescription,CreationDate,Size,SHA256TreeHash
+a1,\"photos, 2023\",2023-01-01T00:00:00Z,1024,hash1
+a2,notes,2023-02-01T00:00:00Z,2048,hash2
+";
+    let inventory = parse_csv_inventory(bytes, "arn", "2024-01-31T10:20:30Z").unwrap();
+    assert_eq!(inventory.inventory_date, "2024-01-31T10:20:30Z");
+    assert_eq!(inventory.archive_list.len(), 2);
+    assert_eq!(
+        inventory.archive_list[0].archive_description,
+        "photos, 2023"
+    );
+    assert_eq!(inventory.archive_list[1].size, 2048);
+}
+
+fn write_csv_inventory(path: &str, inventory: &Vault) -> Result<(), anyhow::Error> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for archive in inventory.archive_list.iter() {
+        writer.serialize(archive)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+impl InventoryFilters {
+    /// Whether the inventory covers only part of the vault
+    fn is_scoped(&self) -> bool {
+        self.start_date.is_some() || self.end_date.is_some() || self.marker.is_some()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.start_date.is_none()
+            && self.end_date.is_none()
+            && self.limit.is_none()
+            && self.marker.is_none()
+    }
+}
+
+pub fn inventory_directory(vault_name: &str) -> String {
+    format!("{}/vault/{}", basmati_directory(), vault_name)
+}
+
+/// Pages of an inventory retrieved with a limit are gathered here until the last one arrives
+fn partial_inventory_path(vault_name: &str) -> String {
+    format!("{}/inventory.partial.json", inventory_directory(vault_name))
+}
+
+/// Inventories limited by dates or started from a marker only cover part of the vault, they are
+/// kept apart from the inventory of the vault, its snapshots and the local index
+fn filtered_inventory_path(vault_name: &str) -> String {
+    format!(
+        "{}/inventory.filtered.json",
+        inventory_directory(vault_name)
+    )
+}
+
+fn filtered_partial_inventory_path(vault_name: &str) -> String {
+    format!(
+        "{}/inventory.filtered.partial.json",
+        inventory_directory(vault_name)
+    )
+}
+
+fn write_inventory_file(path: &str, inventory: &Vault) -> Result<(), anyhow::Error> {
+    let buffer = serde_json::to_vec(inventory)?;
+    fs::write(path, buffer)?;
+    Ok(())
+}
+
+pub fn snapshot_directory(vault_name: &str) -> String {
+    format!("{}/snapshots", inventory_directory(vault_name))
+}
+
+/// Snapshots are named after the date Glacier took the inventory, e.g. `20240131T102030Z`
+fn snapshot_name(inventory: &Vault) -> String {
+    let date = chrono::DateTime::parse_from_rfc3339(&inventory.inventory_date)
+        .map(|x| x.with_timezone(&chrono::Utc))
+        .unwrap_or_else(|_| chrono::Utc::now());
+    date.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Names of the inventory snapshots of a vault, oldest first
+pub fn list_snapshots(vault_name: &str) -> Result<Vec<String>, anyhow::Error> {
+    let mut snapshots: Vec<String> = match fs::read_dir(snapshot_directory(vault_name)) {
+        Ok(entries) => entries
+            .filter_map(Result::ok)
+            .filter_map(|x| {
+                x.file_name()
+                    .to_str()
+                    .and_then(|x| x.strip_suffix(".json"))
+                    .map(String::from)
+            })
+            .collect(),
+        Err(_) => vec![],
+    };
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+pub fn read_snapshot(vault_name: &str, snapshot: &str) -> Result<Vault, anyhow::Error> {
+    let path = format!("{}/{}.json", snapshot_directory(vault_name), snapshot);
+    let bytes = fs::read(&path)
+        .map_err(|_| anyhow!("No snapshot {} for vault {}", snapshot, vault_name))?;
+    Ok(serde_json::from_slice(&bytes)?)
+}
+
+/// Store a complete inventory as the local inventory of a vault, keep a snapshot of it and update
+/// the local index. CSV inventories are additionally written out as `inventory.csv`
+pub fn write_inventory(
+    vault_name: &str,
+    inventory: &Vault,
+    format: InventoryFormat,
+) -> Result<(), anyhow::Error> {
+    let output_directory = inventory_directory(vault_name);
+    fs::create_dir_all(snapshot_directory(vault_name))?;
+    if format == InventoryFormat::Csv {
+        write_csv_inventory(&format!("{}/inventory.csv", output_directory), inventory)?;
+    }
+    write_inventory_file(
+        &format!(
+            "{}/{}.json",
+            snapshot_directory(vault_name),
+            snapshot_name(inventory)
+        ),
+        inventory,
+    )?;
+    write_inventory_file(&format!("{}/inventory.json", output_directory), inventory)?;
+    index_inventory(&mut open_index()?, vault_name, inventory)
+}
+
+#[derive(Debug, Default)]
+pub struct InventoryDiff {
+    pub added: Vec<ArchiveItem>,
+    pub removed: Vec<ArchiveItem>,
+    /// Archives whose description, size or tree hash differ, as `(before, after)`
+    pub changed: Vec<(ArchiveItem, ArchiveItem)>,
+}
+
+pub fn diff_inventories(before: &[ArchiveItem], after: &[ArchiveItem]) -> InventoryDiff {
+    let before_by_id: HashMap<&str, &ArchiveItem> =
+        before.iter().map(|x| (x.archive_id.as_str(), x)).collect();
+    let after_by_id: HashMap<&str, &ArchiveItem> =
+        after.iter().map(|x| (x.archive_id.as_str(), x)).collect();
+    let mut diff = InventoryDiff::default();
+    for archive in after {
+        match before_by_id.get(archive.archive_id.as_str()) {
+            None => diff.added.push(archive.clone()),
+            Some(previous)
+                if previous.archive_description != archive.archive_description
+                    || previous.size != archive.size
+                    || previous.sha256_tree_hash != archive.sha256_tree_hash =>
+            {
+                diff.changed.push(((*previous).clone(), archive.clone()))
+            }
+            Some(_) => {}
+        }
+    }
+    diff.removed = before
+        .iter()
+        .filter(|x| !after_by_id.contains_key(x.archive_id.as_str()))
+        .cloned()
+        .collect();
+    diff
+}
+
+#[test]
+fn test_diff_inventories() {
+    let archive =
+        |id: &str, description: &str| test_archive(id, description, "2024-01-31T10:20:30Z", 1);
+    let before = vec![
+        archive("a", "kept"),
+        archive("b", "removed"),
+        archive("c", "old"),
+    ];
+    let after = vec![
+        archive("a", "kept"),
+        archive("c", "new"),
+        archive("d", "added"),
+    ];
+    let diff = diff_inventories(&before, &after);
+    assert_eq!(diff.added.len(), 1);
+    assert_eq!(diff.added[0].archive_id, "d");
+    assert_eq!(diff.removed.len(), 1);
+    assert_eq!(diff.removed[0].archive_id, "b");
+    assert_eq!(diff.changed.len(), 1);
+    assert_eq!(diff.changed[0].0.archive_description, "old");
+    assert_eq!(diff.changed[0].1.archive_description, "new");
+}
+
+/// Report the archives added, removed and changed between two snapshots, by default the two most
+/// recent ones
+pub fn do_diff(
+    vault_name: &str,
+    from: &Option<String>,
+    to: &Option<String>,
+    list: &bool,
+) -> Result<(), anyhow::Error> {
+    let snapshots = list_snapshots(vault_name)?;
+    if *list {
+        snapshots.iter().for_each(|x| println!("{}", x));
+        return Ok(());
+    }
+    let to = match to {
+        Some(x) => x.clone(),
+        None => snapshots
+            .last()
+            .cloned()
+            .ok_or(anyhow!("No inventory snapshots for vault {}", vault_name))?,
+    };
+    let from = match from {
+        Some(x) => x.clone(),
+        None => snapshots
+            .iter()
+            .rev()
+            .find(|x| x.as_str() < to.as_str())
+            .cloned()
+            .ok_or(anyhow!(
+                "No inventory snapshot older than {} to compare with",
+                to
+            ))?,
+    };
+
+    let diff = diff_inventories(
+        &read_snapshot(vault_name, &from)?.archive_list,
+        &read_snapshot(vault_name, &to)?.archive_list,
+    );
+    println!("comparing {} with {}", from.yellow(), to.yellow());
+    for archive in diff.added.iter() {
+        println!(
+            "{} {} {} {}",
+            "+".green(),
+            archive.creation_date,
+            archive.archive_description,
+            archive.archive_id
+        );
+    }
+    for archive in diff.removed.iter() {
+        println!(
+            "{} {} {} {}",
+            "-".red(),
+            archive.creation_date,
+            archive.archive_description,
+            archive.archive_id
+        );
+    }
+    for (before, after) in diff.changed.iter() {
+        println!(
+            "{} {} {} -> {} {}",
+            "~".yellow(),
+            after.creation_date,
+            before.archive_description,
+            after.archive_description,
+            after.archive_id
+        );
+    }
+    println!(
+        "{} added, {} removed, {} changed",
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    );
+    Ok(())
+}
+
+/// Write out the output of a completed job: inventories become the local inventory of the vault,
+/// archives are written where the user asked for when the job was initiated
+pub async fn collect_job(
+    client: &Client,
+    job: &InitiatedJob,
+    archive_id: Option<&str>,
+    output_dir: &str,
+) -> Result<Status, anyhow::Error> {
+    if job.job_type == JobType::Inventory {
+        if let Some((next_job, _)) =
+            collect_inventory(client, &job.vault, &job.job_id, job.output_path.as_deref()).await?
+        {
+            println!(
+                "more inventory to come for vault {} - run `basmati inventory --pending` again once job {} has completed",
+                job.vault,
+                next_job.yellow()
+            );
+        }
+        return Ok(Status::Done);
+    }
+
+    let output_builder = client
+        .get_job_output()
+        .account_id("-")
+        .vault_name(&job.vault)
+        .job_id(&job.job_id);
+
+    let write_file = match &job.output_path {
+        Some(path) => path.clone(),
+        None => retrieval_output_path(
+            &job.vault,
+            archive_id,
+            &job.job_id,
+            output_dir,
+            &Default::default(),
+        )?,
+    };
+    let file = output_writer(&write_file).await?;
+    if let Ok(Status::Done) = get_job_output(output_builder, file).await {
+        delete_job_from_local(job.job_id.to_owned()).await?;
+        Ok(Status::Done)
+    } else {
+        Ok(Status::Failed)
+    }
+}
+
 pub async fn resolve_all_pending(
     client: &Client,
     job_type: JobType,
+    output_dir: &str,
 ) -> Result<Status, anyhow::Error> {
     delete_expired_jobs_from_local().await?;
     let jobs = get_jobs().await?;
-    let mut pending_jobs = jobs.iter().filter(|&x| x.job_type == job_type).map(|x| {
-        (
-            client
-                .describe_job()
-                .account_id("-")
-                .vault_name(&x.vault)
-                .job_id(&x.job_id),
-            &x.vault,
-        )
-    });
-
-    while let Some((describe_builder, vault)) = pending_jobs.next() {
+    for job in jobs.iter().filter(|&x| x.job_type == job_type) {
+        let describe_builder = client
+            .describe_job()
+            .account_id("-")
+            .vault_name(&job.vault)
+            .job_id(&job.job_id);
         if let Ok((Status::Done, Some(output))) = describe_job_output(&describe_builder).await {
-            let job_id = output.job_id().unwrap();
-            let output_builder = client
-                .get_job_output()
-                .account_id("-")
-                .vault_name(vault)
-                .job_id(output.job_id().unwrap());
-
-            let write_file = match job_type {
-                JobType::Inventory => {
-                    let output_directory = format!("{}/vault/{}", basmati_directory(), &vault);
-                    fs::create_dir_all(&output_directory)
-                        .expect("Could not write to file nor create directory");
-                    format!("{}/inventory.json", &output_directory)
-                }
-                JobType::Retrieval => String::from(job_id),
-            };
-
-            let file = fs::File::create(write_file)?;
-            if let Ok(Status::Done) = get_job_output(output_builder, file).await {
-                delete_job_from_local(job_id.to_owned()).await?;
-            } else {
+            if let Status::Failed =
+                collect_job(client, job, output.archive_id(), output_dir).await?
+            {
                 return Ok(Status::Failed);
             }
         }
@@ -56,79 +414,492 @@ pub async fn resolve_all_pending(
     Ok(Status::Done)
 }
 
-pub async fn do_inventory(client: &Client, vault_name: &String) -> Result<(), anyhow::Error> {
-    match resolve_all_pending(client, JobType::Inventory).await {
-        Ok(Status::Done) => {
-            println!("Finished processing pending inventory jobs");
-        }
-        _ => {}
-    };
-    let init_job = client
+fn inventory_request(
+    client: &Client,
+    vault_name: &str,
+    filters: &InventoryFilters,
+    format: InventoryFormat,
+) -> InitiateJobFluentBuilder {
+    let mut parameters = JobParameters::builder()
+        .r#type("inventory-retrieval")
+        .description(vault_name)
+        .format(format.as_str());
+    if !filters.is_empty() {
+        parameters = parameters.inventory_retrieval_parameters(
+            InventoryRetrievalJobInput::builder()
+                .set_start_date(filters.start_date.clone())
+                .set_end_date(filters.end_date.clone())
+                .set_limit(filters.limit.map(|x| x.to_string()))
+                .set_marker(filters.marker.clone())
+                .build(),
+        );
+    }
+    client
         .initiate_job()
         .account_id("-")
         .vault_name(vault_name)
-        .job_parameters(
-            JobParameters::builder()
-                .r#type("inventory-retrieval")
-                .description(vault_name)
-                .format("JSON")
-                .build(),
+        .job_parameters(parameters.build())
+}
+
+/// The date of the cached inventory of a vault and how many seconds old it is
+pub fn cached_inventory_age(vault_name: &str) -> Result<Option<(String, i64)>, anyhow::Error> {
+    Ok(indexed_inventory_date(&open_index()?, vault_name)?
+        .and_then(|date| seconds_since(&date).map(|age| (date, age))))
+}
+
+pub fn describe_inventory_age(inventory_age: &Option<(String, i64)>) -> String {
+    match inventory_age {
+        Some((date, age)) => format!("inventory of {} ({} old)", date, format_duration(*age)),
+        None => String::from("no inventory yet"),
+    }
+}
+
+/// Print a warning on stderr when the cached inventory of a vault is older than the configured
+/// threshold
+pub fn warn_stale_inventory(vault_name: &str, inventory_age: &Option<(String, i64)>) {
+    if let Some((_, age)) = inventory_age
+        .as_ref()
+        .filter(|(_, age)| read_config().is_ok_and(|x| x.is_stale(*age)))
+    {
+        eprintln!(
+            "{} the cached inventory is {} old - run `basmati inventory -v {}` to refresh it",
+            "warning:".yellow(),
+            format_duration(*age),
+            vault_name
+        );
+    }
+}
+
+/// Describe the age of the cached inventory of a vault, offering to start a new inventory job
+/// when it is older than the configured threshold
+pub async fn check_inventory_age(
+    client: &Client,
+    vault_name: &str,
+) -> Result<String, anyhow::Error> {
+    let inventory_age = cached_inventory_age(vault_name)?;
+    let description = describe_inventory_age(&inventory_age);
+    let Some((_, age)) = inventory_age else {
+        return Ok(description);
+    };
+    if !read_config()?.is_stale(age) {
+        return Ok(description);
+    }
+    if let Some(job) = get_jobs()
+        .await?
+        .into_iter()
+        .find(|x| x.vault == vault_name && x.job_type == JobType::Inventory)
+    {
+        eprintln!(
+            "the inventory of vault {} is {} old, inventory job {} is already pending",
+            vault_name,
+            format_duration(age),
+            job.job_id.yellow()
+        );
+        return Ok(description);
+    }
+    let start_inventory = confirm(
+        format!(
+            "The inventory of vault {} is {} old",
+            vault_name,
+            format_duration(age)
+        ),
+        vec![
+            String::from(" Archives uploaded or deleted since then may be missing or out of date"),
+            String::from(
+                " Start a new inventory job? You can keep using the cached inventory meanwhile",
+            ),
+        ],
+    )?;
+    if start_inventory {
+        let init_output = initiate_inventory(
+            client,
+            vault_name,
+            &InventoryFilters::default(),
+            InventoryFormat::Json,
         )
+        .await?;
+        eprintln!(
+            "inventory job {} recorded - {}",
+            init_output.job_id().unwrap_or_default().yellow(),
+            COLLECT_LATER
+        );
+    }
+    Ok(description)
+}
+
+/// Initiate an inventory job for a vault and record it in the local jobs file
+pub async fn initiate_inventory(
+    client: &Client,
+    vault_name: &str,
+    filters: &InventoryFilters,
+    format: InventoryFormat,
+) -> Result<InitiateJobOutput, anyhow::Error> {
+    let init_output = inventory_request(client, vault_name, filters, format)
+        .send()
+        .await?;
+    // the pages of a scoped inventory are gathered apart from the ones of complete inventories,
+    // starting over from the pages of a scoped inventory that was never collected
+    let partial = filters
+        .is_scoped()
+        .then(|| filtered_partial_inventory_path(vault_name));
+    if let Some(path) = partial.as_deref().filter(|x| Path::new(x).exists()) {
+        fs::remove_file(path)?;
+    }
+    save_job_output(init_output.clone(), JobType::Inventory, partial).await?;
+    Ok(init_output)
+}
+
+async fn fetch_job_output(
+    client: &Client,
+    vault_name: &str,
+    job_id: &str,
+) -> Result<Vec<u8>, anyhow::Error> {
+    let builder = client
+        .get_job_output()
+        .account_id("-")
+        .vault_name(vault_name)
+        .job_id(job_id);
+    let mut buffer = vec![];
+    match get_job_output(builder, &mut buffer).await? {
+        Status::Done => Ok(buffer),
+        _ => Err(anyhow!("failed to get output of job {}", job_id)),
+    }
+}
+
+/// Add a page to the pages gathered so far in the partial inventory. The first page of a complete
+/// inventory has no partial inventory, the first page of a scoped one has a partial inventory that
+/// `initiate_inventory` made sure does not exist yet
+fn add_inventory_page(partial: Option<&str>, page: Vault) -> Result<Vault, anyhow::Error> {
+    match partial {
+        Some(path) if Path::new(path).exists() => {
+            let mut inventory: Vault = serde_json::from_slice(&fs::read(path)?)?;
+            inventory.archive_list.extend(page.archive_list);
+            Ok(inventory)
+        }
+        _ => Ok(page),
+    }
+}
+
+#[test]
+fn test_add_inventory_page() {
+    let page = |ids: &[&str]| Vault {
+        vault_arn: String::from("arn"),
+        inventory_date: String::from("2024-02-01T00:00:00Z"),
+        archive_list: ids
+            .iter()
+            .map(|x| test_archive(x, x, "2024-01-31T10:20:30Z", 1))
+            .collect(),
+    };
+    let ids = |inventory: &Vault| -> Vec<String> {
+        inventory
+            .archive_list
+            .iter()
+            .map(|x| x.archive_id.clone())
+            .collect()
+    };
+    let path = std::env::temp_dir().join(format!("basmati-page-{}.json", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    assert_eq!(
+        ids(&add_inventory_page(None, page(&["a"])).unwrap()),
+        vec!["a"]
+    );
+    assert_eq!(
+        ids(&add_inventory_page(Some(path), page(&["a"])).unwrap()),
+        vec!["a"]
+    );
+    write_inventory_file(path, &page(&["a", "b"])).unwrap();
+    assert_eq!(
+        ids(&add_inventory_page(Some(path), page(&["c"])).unwrap()),
+        vec!["a", "b", "c"]
+    );
+    fs::remove_file(path).unwrap();
+}
+
+/// Store one page of inventory. When Glacier returns a marker because the job was limited, the
+/// page is added to the partial inventory and a job for the next page is initiated and returned
+/// along with the partial inventory it adds to. Otherwise the assembled inventory becomes the
+/// local inventory of the vault, unless it was limited by dates or started from a marker and only
+/// covers part of the vault
+pub async fn store_inventory_page(
+    client: &Client,
+    vault_name: &str,
+    job_id: &str,
+    bytes: &[u8],
+    partial: Option<&str>,
+) -> Result<Option<(String, String)>, anyhow::Error> {
+    let describe_output = client
+        .describe_job()
+        .account_id("-")
+        .vault_name(vault_name)
+        .job_id(job_id)
         .send()
-        .await;
+        .await?;
+    let parameters = describe_output.inventory_retrieval_parameters();
+    let format = InventoryFormat::from_glacier(parameters.and_then(|x| x.format()));
+    let page: Vault = match format {
+        InventoryFormat::Json => serde_json::from_slice(bytes)?,
+        InventoryFormat::Csv => parse_csv_inventory(
+            bytes,
+            describe_output.vault_arn().unwrap_or_default(),
+            describe_output
+                .completion_date()
+                .or(describe_output.creation_date())
+                .unwrap_or_default(),
+        )?,
+    };
+    let scoped = partial == Some(filtered_partial_inventory_path(vault_name).as_str())
+        || parameters.is_some_and(|x| x.start_date().is_some() || x.end_date().is_some());
+    let inventory = add_inventory_page(partial, page)?;
+    delete_job_from_local(job_id.to_owned()).await?;
 
-    match init_job {
+    match parameters.and_then(|x| x.marker()) {
+        Some(marker) => {
+            let partial_path = if scoped {
+                filtered_partial_inventory_path(vault_name)
+            } else {
+                partial_inventory_path(vault_name)
+            };
+            fs::create_dir_all(inventory_directory(vault_name))?;
+            write_inventory_file(&partial_path, &inventory)?;
+            let filters = InventoryFilters {
+                start_date: parameters.and_then(|x| x.start_date()).map(String::from),
+                end_date: parameters.and_then(|x| x.end_date()).map(String::from),
+                limit: parameters
+                    .and_then(|x| x.limit())
+                    .and_then(|x| x.parse().ok()),
+                marker: Some(String::from(marker)),
+            };
+            let init_output = inventory_request(client, vault_name, &filters, format)
+                .send()
+                .await?;
+            let next_job = String::from(init_output.job_id().unwrap_or_default());
+            save_job_output(init_output, JobType::Inventory, Some(partial_path.clone())).await?;
+            println!(
+                "{} archives so far, initiated job for the next page of the inventory",
+                inventory.archive_list.len()
+            );
+            Ok(Some((next_job, partial_path)))
+        }
+        None if scoped => {
+            fs::create_dir_all(inventory_directory(vault_name))?;
+            write_inventory_file(&filtered_inventory_path(vault_name), &inventory)?;
+            if let Some(path) = partial.filter(|x| Path::new(x).exists()) {
+                fs::remove_file(path)?;
+            }
+            println!(
+                "{}: {} archives of vault {} in {} - it only covers part of the vault and is not used as its inventory",
+                "filtered inventory stored".green(),
+                inventory.archive_list.len(),
+                vault_name,
+                filtered_inventory_path(vault_name)
+            );
+            Ok(None)
+        }
+        None => {
+            write_inventory(vault_name, &inventory, format)?;
+            if let Some(path) = partial.filter(|x| Path::new(x).exists()) {
+                fs::remove_file(path)?;
+            }
+            println!(
+                "{}: {} archives in vault {}",
+                "inventory stored".green(),
+                inventory.archive_list.len(),
+                vault_name
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Download the output of a completed inventory job and store it, see `store_inventory_page`
+pub async fn collect_inventory(
+    client: &Client,
+    vault_name: &str,
+    job_id: &str,
+    partial: Option<&str>,
+) -> Result<Option<(String, String)>, anyhow::Error> {
+    let bytes = fetch_job_output(client, vault_name, job_id).await?;
+    store_inventory_page(client, vault_name, job_id, &bytes, partial).await
+}
+
+/// An inventory job of `inventory --all`, stored as the inventory of its vault once it completes
+struct PendingInventory {
+    vault: String,
+    job_id: String,
+}
+
+impl InFlight for PendingInventory {
+    fn vault(&self) -> &str {
+        &self.vault
+    }
+    fn job_id(&self) -> &str {
+        &self.job_id
+    }
+}
+
+/// Initiate inventory jobs for every vault of the account in parallel and collect all of them
+pub async fn do_inventory_all(
+    client: &Client,
+    format: &InventoryFormat,
+    no_wait: &bool,
+    concurrency: &usize,
+) -> Result<(), anyhow::Error> {
+    let vaults = list_all_vaults(client).await?;
+    let mut requests = JoinSet::new();
+    for vault in vaults.iter().filter_map(|x| x.vault_name()) {
+        let vault = String::from(vault);
+        let request = inventory_request(client, &vault, &InventoryFilters::default(), *format);
+        requests.spawn(async move { (vault, request.send().await) });
+    }
+
+    let mut pending = vec![];
+    let mut failures = 0;
+    while let Some(result) = requests.join_next().await {
+        let (vault, init_output) = result?;
+        match init_output {
+            Ok(init_output) => {
+                println!("{}: {}", "initiated inventory job".green(), vault);
+                let job_id = String::from(init_output.job_id().unwrap_or_default());
+                // the local jobs file is written sequentially to not lose concurrent updates
+                save_job_output(init_output, JobType::Inventory, None).await?;
+                pending.push(PendingInventory { vault, job_id });
+            }
+            Err(reason) => {
+                eprintln!("initiation failed for vault {}: {}", vault, reason);
+                failures += 1;
+            }
+        }
+    }
+
+    if *no_wait {
+        println!(
+            "{} inventory jobs recorded - {}",
+            pending.len(),
+            COLLECT_LATER
+        );
+        if failures > 0 {
+            return Err(anyhow!("{} inventory jobs failed to initiate", failures));
+        }
+        return Ok(());
+    }
+
+    let semaphore = Arc::new(Semaphore::new((*concurrency).max(1)));
+    let mut downloads = JoinSet::new();
+    let mut describe_failures = HashMap::new();
+    while !pending.is_empty() {
+        let partitioned = partition_completed(client, pending, &mut describe_failures).await;
+        failures += partitioned.failed.len();
+        for job in partitioned.completed {
+            let client = client.clone();
+            let semaphore = semaphore.clone();
+            downloads.spawn(async move {
+                let bytes = async {
+                    let _permit = semaphore.acquire_owned().await?;
+                    fetch_job_output(&client, &job.vault, &job.job_id).await
+                }
+                .await;
+                (job, bytes)
+            });
+        }
+        pending = partitioned.still_pending;
+        sleep_unless_done(pending.len()).await;
+    }
+
+    while let Some(result) = downloads.join_next().await {
+        let (job, bytes) = result?;
+        // pages are stored one at a time as storing them writes the local jobs file
+        let stored = match bytes {
+            Ok(bytes) => store_inventory_page(client, &job.vault, &job.job_id, &bytes, None).await,
+            Err(reason) => Err(reason),
+        };
+        if let Err(reason) = stored {
+            eprintln!(
+                "failed to collect inventory of vault {}: {}",
+                job.vault, reason
+            );
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        return Err(anyhow!(
+            "{} inventories failed to initiate or download",
+            failures
+        ));
+    }
+    Ok(())
+}
+
+pub async fn do_inventory(
+    client: &Client,
+    vault_name: &Option<String>,
+    filters: &InventoryFilters,
+    format: &InventoryFormat,
+    no_wait: &bool,
+    pending: &bool,
+) -> Result<(), anyhow::Error> {
+    if *pending {
+        match resolve_all_pending(client, JobType::Inventory, ".").await {
+            Ok(Status::Done) => {
+                println!("Finished processing pending inventory jobs");
+                return Ok(());
+            }
+            _ => {
+                println!("Something went wrong");
+                return Ok(());
+            }
+        }
+    }
+    let vault_name = vault_name
+        .as_ref()
+        .expect("Expected vault_name to be defined");
+
+    if let Ok(Status::Done) = resolve_all_pending(client, JobType::Inventory, ".").await {
+        println!("Finished processing pending inventory jobs");
+    };
+
+    match initiate_inventory(client, vault_name, filters, *format).await {
         Ok(init_ouput) => {
             println!("initiated inventory job successfuly...");
-            save_job_output(init_ouput.clone(), JobType::Inventory)
-                .await
-                .expect("Was not able to save metadata");
-
-            let describe_builder = client
-                .describe_job()
-                .account_id("-")
-                .vault_name(vault_name)
-                .job_id(init_ouput.job_id().unwrap());
-
-            if let Ok(mut describe_output) = describe_job_loop(describe_builder.clone()).await {
-                println!("job {} completed", describe_output.job_id.as_mut().unwrap());
-                let output_directory = format!("{}/vault/{}", basmati_directory(), &vault_name);
-
-                fs::create_dir_all(&output_directory)
-                    .expect("Could not write to file nor create directory");
-                if let Ok(file) = fs::File::create(format!("{}/inventory.json", &output_directory))
-                {
-                    let job_id = describe_output.job_id().unwrap();
-                    let output_builder = client
-                        .get_job_output()
-                        .account_id("-")
-                        .vault_name(vault_name)
-                        .job_id(describe_output.job_id().unwrap());
-
-                    match get_job_output(output_builder, file).await {
-                        Ok(Status::Failed) => Ok(()),
-                        Ok(Status::Pending) => Ok(()),
-                        Ok(Status::Done) => {
+            if *no_wait {
+                println!(
+                    "job {} recorded - {}",
+                    init_ouput.job_id().unwrap_or_default().yellow(),
+                    COLLECT_LATER
+                );
+                return Ok(());
+            }
+
+            let mut job_id = String::from(init_ouput.job_id().unwrap());
+            let mut partial = filters
+                .is_scoped()
+                .then(|| filtered_partial_inventory_path(vault_name));
+            loop {
+                let describe_builder = client
+                    .describe_job()
+                    .account_id("-")
+                    .vault_name(vault_name)
+                    .job_id(&job_id);
+
+                if let Ok(mut describe_output) = describe_job_loop(describe_builder.clone()).await {
+                    println!("job {} completed", describe_output.job_id.as_mut().unwrap());
+                    match collect_inventory(client, vault_name, &job_id, partial.as_deref()).await?
+                    {
+                        Some((next_job, partial_path)) => {
+                            job_id = next_job;
+                            partial = Some(partial_path);
+                        }
+                        None => {
                             println!(
                                 "inventory job completed successfuly for vault {}",
                                 vault_name
                             );
-                            delete_job_from_local(job_id.to_owned()).await?;
-                            Ok(())
-                        }
-                        Err(reason) => {
-                            println!("failed to get inventory output {}", reason);
-                            Ok(())
+                            break Ok(());
                         }
                     }
                 } else {
-                    Err(anyhow!(format!(
-                        "Could not create inventory file in {}",
-                        &output_directory
-                    )))
+                    break Err(anyhow!(format!("inventory describe job failed")));
                 }
-            } else {
-                Err(anyhow!(format!("inventory describe job failed")))
             }
         }
         Err(reason) => {
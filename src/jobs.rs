@@ -0,0 +1,539 @@
+use crate::config::read_config;
+use crate::inventory::{collect_inventory, collect_job};
+use crate::keymap::{Command, Keymap};
+use crate::list_vaults::list_all_vaults;
+use crate::ls::SortKey;
+use crate::shared::{
+    abort, delete_job_from_local, enter_terminal, format_duration, get_job_output, get_jobs,
+    is_interrupt, output_writer, retrieval_output_path, seconds_since, select_multiple, Cursor,
+    Events, InitiatedJob, JobType, Selectable, Status, TWENTY_FOUR_HOURS,
+};
+use crate::theme::Theme;
+use anyhow::anyhow;
+use aws_sdk_glacier::operation::describe_job::DescribeJobOutput;
+use aws_sdk_glacier::types::{GlacierJobDescription, StatusCode};
+use aws_sdk_glacier::Client;
+use colored::Colorize;
+use crossterm::event::{self, Event, KeyEventKind};
+use ratatui::layout::{Constraint, Direction, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, List, ListItem, Paragraph};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+impl Selectable for GlacierJobDescription {
+    fn id(&self) -> &str {
+        self.job_id().unwrap_or_default()
+    }
+    fn label(&self) -> String {
+        format!(
+            "{:<10} {} {} {}",
+            JobType::from_action(self.action()).map_or("other", |x| x.label()),
+            self.creation_date().unwrap_or_default(),
+            self.job_description()
+                .or(self.archive_id())
+                .unwrap_or_default(),
+            self.job_id().unwrap_or_default()
+        )
+    }
+    fn size(&self) -> Option<i64> {
+        self.archive_size_in_bytes()
+            .or(self.inventory_size_in_bytes())
+    }
+    fn compare(&self, other: &Self, key: SortKey) -> Ordering {
+        match key {
+            SortKey::Date => self.creation_date().cmp(&other.creation_date()),
+            SortKey::Size => self.size().cmp(&other.size()),
+            SortKey::Description => self.job_description().cmp(&other.job_description()),
+        }
+    }
+    fn details(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("job id", self.job_id().unwrap_or_default().to_string()),
+            ("archive id", self.archive_id().unwrap_or("-").to_string()),
+            (
+                "tree hash",
+                self.sha256_tree_hash().unwrap_or("-").to_string(),
+            ),
+        ]
+    }
+}
+
+pub async fn list_remote_jobs(
+    client: &Client,
+    vault_name: &str,
+) -> Result<Vec<GlacierJobDescription>, anyhow::Error> {
+    let mut pages = client
+        .list_jobs()
+        .account_id("-")
+        .vault_name(vault_name)
+        .into_paginator()
+        .items()
+        .send();
+    let mut jobs = vec![];
+    while let Some(job) = pages.next().await {
+        jobs.push(job?);
+    }
+    Ok(jobs)
+}
+
+fn remote_status(job: &GlacierJobDescription) -> String {
+    job.status_code()
+        .map(|x| x.as_str().to_lowercase())
+        .unwrap_or_else(|| String::from("unknown"))
+}
+
+/// Glacier keeps job output around for 24 hours after the job completes
+fn expires_in(completed: bool, completion_date: Option<&str>) -> Option<String> {
+    match completion_date.and_then(seconds_since) {
+        Some(elapsed) if completed => Some(format_duration(TWENTY_FOUR_HOURS - elapsed)),
+        _ => None,
+    }
+}
+
+fn remote_expiry(job: &GlacierJobDescription) -> String {
+    expires_in(job.completed(), job.completion_date()).unwrap_or_else(|| String::from("-"))
+}
+
+fn print_row(job_type: &str, vault: &str, age: &str, status: &str, expiry: &str, job_id: &str) {
+    println!(
+        "{:<10} {:<20} {:>8} {:<11} {:>8}  {}",
+        job_type, vault, age, status, expiry, job_id
+    );
+}
+
+pub async fn do_list(client: &Client, vault_name: &Option<String>) -> Result<(), anyhow::Error> {
+    let local_jobs = get_jobs().await?;
+    let mut vaults: Vec<String> = match vault_name {
+        Some(name) => vec![name.clone()],
+        None => match list_all_vaults(client).await {
+            Ok(vaults) => vaults
+                .iter()
+                .filter_map(|x| x.vault_name().map(String::from))
+                .collect(),
+            Err(reason) => {
+                eprintln!(
+                    "listing vaults failed, only showing the vaults of local jobs - {}",
+                    reason
+                );
+                local_jobs.iter().map(|x| x.vault.clone()).collect()
+            }
+        },
+    };
+    vaults.sort();
+    vaults.dedup();
+
+    let mut remote_jobs = vec![];
+    for vault in vaults.iter() {
+        match list_remote_jobs(client, vault).await {
+            Ok(jobs) => remote_jobs.extend(jobs.into_iter().map(|x| (vault.clone(), x))),
+            Err(reason) => eprintln!("listing jobs for vault {} failed - {}", vault, reason),
+        }
+    }
+
+    println!("{}", "local jobs".green());
+    print_row("TYPE", "VAULT", "AGE", "STATUS", "EXPIRES", "JOB ID");
+    for job in local_jobs
+        .iter()
+        .filter(|x| vault_name.as_ref().is_none_or(|name| &x.vault == name))
+    {
+        let age = chrono::Utc::now().timestamp() - job.timestamp;
+        let remote = remote_jobs
+            .iter()
+            .find(|(_, x)| x.job_id() == Some(job.job_id.as_str()));
+        // the output expires 24 hours after the job completed, which only Glacier knows
+        let (status, expiry) = match remote {
+            Some((_, x)) => (remote_status(x), remote_expiry(x)),
+            None => (String::from("unknown"), String::from("-")),
+        };
+        print_row(
+            job.job_type.label(),
+            &job.vault,
+            &format_duration(age),
+            &status,
+            &expiry,
+            &job.job_id,
+        );
+    }
+
+    println!("{}", "remote jobs".green());
+    print_row("TYPE", "VAULT", "AGE", "STATUS", "EXPIRES", "JOB ID");
+    for (vault, job) in remote_jobs.iter().filter(|(_, x)| {
+        !local_jobs
+            .iter()
+            .any(|y| Some(y.job_id.as_str()) == x.job_id())
+    }) {
+        let job_type = JobType::from_action(job.action()).map_or("other", |x| x.label());
+        let age = job
+            .creation_date()
+            .and_then(seconds_since)
+            .map_or(String::from("-"), format_duration);
+        print_row(
+            job_type,
+            vault,
+            &age,
+            &remote_status(job),
+            &remote_expiry(job),
+            job.job_id().unwrap_or_default(),
+        );
+    }
+    Ok(())
+}
+
+pub async fn do_describe(
+    client: &Client,
+    job_id: &String,
+    vault_name: &Option<String>,
+) -> Result<(), anyhow::Error> {
+    let vault = match vault_name {
+        Some(name) => name.clone(),
+        None => get_jobs()
+            .await?
+            .into_iter()
+            .find(|x| &x.job_id == job_id)
+            .map(|x| x.vault)
+            .ok_or(anyhow!(
+                "Job {} is not in the local jobs file - pass the vault name explicitly",
+                job_id
+            ))?,
+    };
+
+    let output = client
+        .describe_job()
+        .account_id("-")
+        .vault_name(&vault)
+        .job_id(job_id)
+        .send()
+        .await?;
+
+    let field = |name: &str, value: Option<String>| {
+        println!(
+            "{:<18} {}",
+            name.yellow(),
+            value.unwrap_or_else(|| String::from("-"))
+        )
+    };
+    field("job id", output.job_id().map(String::from));
+    field("vault", Some(vault.clone()));
+    field("type", output.action().map(|x| x.as_str().to_string()));
+    field(
+        "status",
+        output.status_code().map(|x| x.as_str().to_string()),
+    );
+    field("status message", output.status_message().map(String::from));
+    field("description", output.job_description().map(String::from));
+    field("created", output.creation_date().map(String::from));
+    field("completed", output.completion_date().map(String::from));
+    field(
+        "expires in",
+        expires_in(output.completed(), output.completion_date()),
+    );
+    field("archive id", output.archive_id().map(String::from));
+    field(
+        "archive size",
+        output.archive_size_in_bytes().map(|x| x.to_string()),
+    );
+    field(
+        "inventory size",
+        output.inventory_size_in_bytes().map(|x| x.to_string()),
+    );
+    field("tree hash", output.sha256_tree_hash().map(String::from));
+    Ok(())
+}
+
+pub async fn do_forget(job_id: &String) -> Result<(), anyhow::Error> {
+    if !get_jobs().await?.iter().any(|x| &x.job_id == job_id) {
+        return Err(anyhow!("Job {} is not in the local jobs file", job_id));
+    }
+    delete_job_from_local(job_id.to_owned()).await?;
+    println!("{}: {}", "forgot job".yellow(), job_id);
+    Ok(())
+}
+
+/// Download the output of jobs that were started outside of basmati, e.g. from the AWS console
+pub async fn do_adopt(
+    client: &Client,
+    vault_name: &String,
+    output_dir: &str,
+) -> Result<(), anyhow::Error> {
+    let jobs: Vec<GlacierJobDescription> = list_remote_jobs(client, vault_name)
+        .await?
+        .into_iter()
+        .filter(|x| {
+            x.completed()
+                && x.status_code() == Some(&StatusCode::Succeeded)
+                && JobType::from_action(x.action()).is_some()
+        })
+        .collect();
+    if jobs.is_empty() {
+        println!("No completed jobs found for vault {}", vault_name);
+        return Ok(());
+    }
+
+    let keys = Keymap::new(&read_config()?);
+    let selected = select_multiple(
+        Events::new(jobs),
+        &format!(
+            "Completed jobs | {} to select {} to download",
+            keys.label(Command::Toggle),
+            keys.label(Command::Confirm)
+        ),
+    )?;
+    if selected.is_empty() {
+        return Err(anyhow!("You must select a job!"));
+    }
+
+    let mut failures = 0;
+    for job in selected {
+        let job_id = job.job_id().unwrap();
+        if JobType::from_action(job.action()) == Some(JobType::Inventory) {
+            collect_inventory(client, vault_name, job_id, None).await?;
+            continue;
+        }
+        let write_file = retrieval_output_path(
+            vault_name,
+            job.archive_id(),
+            job_id,
+            output_dir,
+            &Default::default(),
+        )?;
+        let builder = client
+            .get_job_output()
+            .account_id("-")
+            .vault_name(vault_name)
+            .job_id(job_id);
+        match get_job_output(builder, output_writer(&write_file).await?).await {
+            Ok(Status::Done) => println!("{}: {}", "adopted job".green(), write_file),
+            Ok(_) => {
+                eprintln!("failed to adopt job {}", job_id);
+                failures += 1;
+            }
+            Err(reason) => {
+                eprintln!("failed to adopt job {}, {:?}", job_id, reason);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        return Err(anyhow!("{} jobs failed to download", failures));
+    }
+    Ok(())
+}
+
+/// Glacier does not report how far along a job is, progress is estimated from how long jobs
+/// usually take
+const TYPICAL_JOB_DURATION: i64 = 4 * 60 * 60;
+
+fn progress_bar(job: &InitiatedJob, remote: Option<&DescribeJobOutput>) -> String {
+    let done = match remote.map(|x| x.status_code()) {
+        Some(Some(StatusCode::Succeeded)) => 10,
+        Some(Some(StatusCode::Failed)) => 0,
+        _ => {
+            let age = chrono::Utc::now().timestamp() - job.timestamp;
+            (age * 10 / TYPICAL_JOB_DURATION).clamp(0, 9) as usize
+        }
+    };
+    format!("[{}{}]", "#".repeat(done), "-".repeat(10 - done))
+}
+
+fn watch_row(job: &InitiatedJob, remote: Option<&Result<DescribeJobOutput, String>>) -> String {
+    let output = remote.and_then(|x| x.as_ref().ok());
+    let status = match remote {
+        Some(Ok(x)) => x
+            .status_code()
+            .map_or(String::from("unknown"), |x| x.as_str().to_lowercase()),
+        Some(Err(_)) => String::from("error"),
+        None => String::from("polling"),
+    };
+    let expiry = output
+        .and_then(|x| expires_in(x.completed(), x.completion_date()))
+        .unwrap_or_else(|| String::from("-"));
+    format!(
+        "{:<10} {:<20} {:>8} {:<11} {} {:>8}  {}",
+        job.job_type.label(),
+        job.vault,
+        format_duration(chrono::Utc::now().timestamp() - job.timestamp),
+        status,
+        progress_bar(job, output),
+        expiry,
+        job.job_id
+    )
+}
+
+async fn poll_jobs(
+    client: &Client,
+    jobs: &[InitiatedJob],
+) -> HashMap<String, Result<DescribeJobOutput, String>> {
+    let mut statuses = HashMap::new();
+    for job in jobs {
+        let output = client
+            .describe_job()
+            .account_id("-")
+            .vault_name(&job.vault)
+            .job_id(&job.job_id)
+            .send()
+            .await
+            .map_err(|x| x.to_string());
+        statuses.insert(job.job_id.clone(), output);
+    }
+    statuses
+}
+
+/// Download the output of a completed job while the dashboard hands over the terminal
+async fn collect_watched_job(
+    client: &Client,
+    job: &InitiatedJob,
+    remote: Option<&Result<DescribeJobOutput, String>>,
+    output_dir: &str,
+) -> String {
+    let Some(Ok(output)) = remote.filter(|x| {
+        x.as_ref()
+            .is_ok_and(|x| x.status_code() == Some(&StatusCode::Succeeded))
+    }) else {
+        return format!("job {} has not completed yet", job.job_id);
+    };
+    match collect_job(client, job, output.archive_id(), output_dir).await {
+        Ok(Status::Done) => format!("collected {} job {}", job.job_type.label(), job.job_id),
+        Ok(_) => format!("downloading job {} failed", job.job_id),
+        Err(reason) => format!("collecting job {} failed - {}", job.job_id, reason),
+    }
+}
+
+/// Live dashboard of the local jobs, polled every `interval` seconds
+pub async fn do_watch(
+    client: &Client,
+    interval: u64,
+    output_dir: &str,
+) -> Result<(), anyhow::Error> {
+    let config = read_config()?;
+    let keys = Keymap::new(&config);
+    let theme = Theme::new(&config)?;
+    let mut jobs = Events::new(get_jobs().await?);
+    let mut statuses = HashMap::new();
+    let mut last_poll: Option<Instant> = None;
+    let mut status_line = String::new();
+    let mut terminal = enter_terminal()?;
+    loop {
+        if last_poll.is_none_or(|x| x.elapsed() >= Duration::from_secs(interval)) {
+            let local_jobs: Vec<InitiatedJob> = jobs.visible_items().cloned().collect();
+            statuses = poll_jobs(client, &local_jobs).await;
+            last_poll = Some(Instant::now());
+        }
+        let poll_error = jobs
+            .choose()
+            .and_then(|x| statuses.get(&x.job_id))
+            .and_then(|x| x.as_ref().err())
+            .map(|x| format!("polling the highlighted job failed - {}", x));
+        terminal.draw(|frame| {
+            let areas = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Length(1), Constraint::Min(3), Constraint::Length(1)])
+                .split(frame.size());
+            frame.render_widget(
+                Paragraph::new(format!(
+                    "   {:<10} {:<20} {:>8} {:<11} {:<12} {:>8}  {}",
+                    "TYPE", "VAULT", "AGE", "STATUS", "PROGRESS", "EXPIRES", "JOB ID"
+                ))
+                .style(theme.accent.add_modifier(Modifier::BOLD)),
+                areas[0],
+            );
+            let rows: Vec<ListItem> = jobs
+                .visible_items()
+                .map(|x| ListItem::new(watch_row(x, statuses.get(&x.job_id))))
+                .collect();
+            let next_poll = last_poll.map_or(0, |x| {
+                interval.saturating_sub(x.elapsed().as_secs())
+            });
+            let list = List::new(rows)
+                .block(
+                    Block::default()
+                        .title(format!("Jobs | next poll in {}s", next_poll))
+                        .style(theme.border)
+                        .borders(Borders::ALL),
+                )
+                .highlight_style(Style::new().add_modifier(Modifier::ITALIC | Modifier::BOLD))
+                .highlight_symbol("->");
+            frame.render_stateful_widget(list, areas[1], jobs.state());
+            let hint = match &poll_error {
+                _ if !status_line.is_empty() => status_line.clone(),
+                Some(reason) => reason.clone(),
+                None => format!(
+                    "{} download the highlighted job | {} download all completed jobs | {} poll now | {} quit",
+                    keys.label(Command::Confirm),
+                    keys.label(Command::DownloadAll),
+                    keys.label(Command::Refresh),
+                    keys.label(Command::Quit)
+                ),
+            };
+            frame.render_widget(Paragraph::new(hint).style(theme.accent), areas[2]);
+        })?;
+
+        if !event::poll(std::time::Duration::from_millis(250))? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        if is_interrupt(&key) {
+            abort();
+        }
+        status_line.clear();
+        let command = keys.command(
+            &key,
+            &[
+                Command::Quit,
+                Command::Down,
+                Command::Up,
+                Command::Refresh,
+                Command::Confirm,
+                Command::Download,
+                Command::DownloadAll,
+            ],
+        );
+        let selected: Vec<InitiatedJob> = match command {
+            Some(Command::Quit) => break,
+            Some(Command::Down) => {
+                jobs.next();
+                continue;
+            }
+            Some(Command::Up) => {
+                jobs.previous();
+                continue;
+            }
+            Some(Command::Refresh) => {
+                last_poll = None;
+                continue;
+            }
+            Some(Command::Confirm | Command::Download) => jobs.choose().into_iter().collect(),
+            Some(Command::DownloadAll) => jobs
+                .visible_items()
+                .filter(|x| {
+                    statuses.get(&x.job_id).is_some_and(|x| {
+                        x.as_ref()
+                            .is_ok_and(|x| x.status_code() == Some(&StatusCode::Succeeded))
+                    })
+                })
+                .cloned()
+                .collect(),
+            _ => continue,
+        };
+        if selected.is_empty() {
+            continue;
+        }
+        drop(terminal);
+        let mut results = vec![];
+        for job in selected.iter() {
+            results.push(
+                collect_watched_job(client, job, statuses.get(&job.job_id), output_dir).await,
+            );
+        }
+        status_line = results.join(" | ");
+        terminal = enter_terminal()?;
+        jobs = Events::new(get_jobs().await?);
+    }
+    Ok(())
+}
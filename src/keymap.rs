@@ -0,0 +1,255 @@
+use crate::config::Config;
+use anyhow::anyhow;
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Everything a key can do in the TUI screens, each screen only listens to the commands it
+/// understands so the same key can mean different things on different screens
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Command {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    First,
+    Last,
+    Toggle,
+    SelectAll,
+    Invert,
+    Search,
+    NextMatch,
+    PreviousMatch,
+    SortDate,
+    SortSize,
+    SortDescription,
+    Confirm,
+    /// Leave the search, dropping the query
+    Cancel,
+    /// Remove the last character of the search
+    Erase,
+    Quit,
+    Refresh,
+    NextPane,
+    Inventory,
+    Download,
+    DownloadAll,
+    Upload,
+    Delete,
+    CollectJobs,
+}
+
+impl Command {
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Command::Up => &["k", "Up"],
+            Command::Down => &["j", "Down"],
+            Command::PageUp => &["PageUp"],
+            Command::PageDown => &["PageDown"],
+            Command::First => &["g", "Home"],
+            Command::Last => &["G", "End"],
+            Command::Toggle => &["Space"],
+            Command::SelectAll => &["a"],
+            Command::Invert => &["i"],
+            Command::Search => &["/"],
+            Command::NextMatch => &["n"],
+            Command::PreviousMatch => &["N"],
+            Command::SortDate => &["1"],
+            Command::SortSize => &["2"],
+            Command::SortDescription => &["3"],
+            Command::Confirm => &["Enter"],
+            Command::Cancel => &["Esc"],
+            Command::Erase => &["Backspace"],
+            Command::Quit => &["q", "Esc"],
+            Command::Refresh => &["r"],
+            Command::NextPane => &["Tab"],
+            Command::Inventory => &["i"],
+            Command::Download => &["d"],
+            Command::DownloadAll => &["a"],
+            Command::Upload => &["u"],
+            Command::Delete => &["x"],
+            Command::CollectJobs => &["p"],
+        }
+    }
+}
+
+/// A key as written in the config file, e.g. `j`, `PageDown` or `ctrl-n`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeySpec {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl TryFrom<String> for KeySpec {
+    type Error = anyhow::Error;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut key = value.as_str();
+        loop {
+            if let Some(rest) = key.strip_prefix("ctrl-") {
+                modifiers |= KeyModifiers::CONTROL;
+                key = rest;
+            } else if let Some(rest) = key.strip_prefix("alt-") {
+                modifiers |= KeyModifiers::ALT;
+                key = rest;
+            } else {
+                break;
+            }
+        }
+        let code = match key {
+            "Up" => KeyCode::Up,
+            "Down" => KeyCode::Down,
+            "Left" => KeyCode::Left,
+            "Right" => KeyCode::Right,
+            "PageUp" => KeyCode::PageUp,
+            "PageDown" => KeyCode::PageDown,
+            "Home" => KeyCode::Home,
+            "End" => KeyCode::End,
+            "Enter" => KeyCode::Enter,
+            "Esc" => KeyCode::Esc,
+            "Tab" => KeyCode::Tab,
+            "Backspace" => KeyCode::Backspace,
+            "Space" => KeyCode::Char(' '),
+            _ => {
+                let mut chars = key.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(x), None) => KeyCode::Char(x),
+                    _ => return Err(anyhow!("Unknown key {}", value)),
+                }
+            }
+        };
+        Ok(KeySpec { code, modifiers })
+    }
+}
+
+impl From<KeySpec> for String {
+    fn from(key: KeySpec) -> Self {
+        let name = match key.code {
+            KeyCode::Char(' ') => String::from("Space"),
+            KeyCode::Char(x) => x.to_string(),
+            KeyCode::PageUp => String::from("PageUp"),
+            KeyCode::PageDown => String::from("PageDown"),
+            KeyCode::Backspace => String::from("Backspace"),
+            code => format!("{:?}", code),
+        };
+        let mut prefix = String::new();
+        if key.modifiers.contains(KeyModifiers::CONTROL) {
+            prefix.push_str("ctrl-");
+        }
+        if key.modifiers.contains(KeyModifiers::ALT) {
+            prefix.push_str("alt-");
+        }
+        format!("{}{}", prefix, name)
+    }
+}
+
+impl KeySpec {
+    fn matches(&self, key: &KeyEvent) -> bool {
+        // shift is already part of the character, `G` arrives as shift-G
+        let modifiers = key.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT);
+        self.code == key.code && self.modifiers == modifiers
+    }
+}
+
+/// The default bindings with the ones of the config file on top, a command bound in the config
+/// file loses its default keys
+pub struct Keymap {
+    bindings: HashMap<Command, Vec<KeySpec>>,
+}
+
+impl Keymap {
+    pub fn new(config: &Config) -> Keymap {
+        let bindings = |command: Command| match config.keys.get(&command) {
+            Some(keys) => keys.clone(),
+            None => command
+                .default_keys()
+                .iter()
+                .map(|x| KeySpec::try_from(x.to_string()).expect("invalid default key"))
+                .collect(),
+        };
+        Keymap {
+            bindings: ALL_COMMANDS.iter().map(|x| (*x, bindings(*x))).collect(),
+        }
+    }
+
+    /// The first of `commands` bound to the key
+    pub fn command(&self, key: &KeyEvent, commands: &[Command]) -> Option<Command> {
+        commands.iter().copied().find(|command| {
+            self.bindings
+                .get(command)
+                .is_some_and(|keys| keys.iter().any(|x| x.matches(key)))
+        })
+    }
+
+    /// How to show the first key of a command in hints, e.g. `<Space>`
+    pub fn label(&self, command: Command) -> String {
+        match self.bindings.get(&command).and_then(|x| x.first()) {
+            Some(key) => match String::from(key.clone()) {
+                name if name.chars().count() == 1 => name,
+                name => format!("<{}>", name),
+            },
+            None => String::from("(unbound)"),
+        }
+    }
+}
+
+const ALL_COMMANDS: [Command; 27] = [
+    Command::Up,
+    Command::Down,
+    Command::PageUp,
+    Command::PageDown,
+    Command::First,
+    Command::Last,
+    Command::Toggle,
+    Command::SelectAll,
+    Command::Invert,
+    Command::Search,
+    Command::NextMatch,
+    Command::PreviousMatch,
+    Command::SortDate,
+    Command::SortSize,
+    Command::SortDescription,
+    Command::Confirm,
+    Command::Cancel,
+    Command::Erase,
+    Command::Quit,
+    Command::Refresh,
+    Command::NextPane,
+    Command::Inventory,
+    Command::Download,
+    Command::DownloadAll,
+    Command::Upload,
+    Command::Delete,
+    Command::CollectJobs,
+];
+
+#[test]
+fn test_keymap_overrides_defaults() {
+    let config: Config =
+        serde_json::from_str(r#"{"keys": {"down": ["ctrl-n", "Down"], "quit": ["x"]}}"#).unwrap();
+    let keys = Keymap::new(&config);
+    let key = |code, modifiers| KeyEvent::new(code, modifiers);
+    let listening = [Command::Up, Command::Down, Command::Last, Command::Quit];
+    assert_eq!(
+        keys.command(&key(KeyCode::Char('n'), KeyModifiers::CONTROL), &listening),
+        Some(Command::Down)
+    );
+    assert_eq!(
+        keys.command(&key(KeyCode::Char('j'), KeyModifiers::NONE), &listening),
+        None
+    );
+    assert_eq!(
+        keys.command(&key(KeyCode::Char('G'), KeyModifiers::SHIFT), &listening),
+        Some(Command::Last)
+    );
+    assert_eq!(
+        keys.command(&key(KeyCode::Char('q'), KeyModifiers::NONE), &listening),
+        None
+    );
+    assert_eq!(keys.label(Command::Down), "<ctrl-n>");
+    assert_eq!(keys.label(Command::Toggle), "<Space>");
+    assert!(serde_json::from_str::<Config>(r#"{"keys": {"up": ["Upp"]}}"#).is_err());
+}
@@ -1,28 +1,117 @@
+use crate::index::{
+    indexed_archive_count, indexed_inventory_date, open_index, pending_deletions, pending_uploads,
+};
+use crate::inventory::{cached_inventory_age, describe_inventory_age};
+use crate::shared::human_size;
+use aws_sdk_glacier::types::DescribeVaultOutput;
 use aws_sdk_glacier::Client;
+use clap::ValueEnum;
+use colored::Colorize;
+use serde::Serialize;
 
-pub async fn do_listing(client: &Client) -> Result<(), anyhow::Error> {
-    //todo implement pagination
-    let job = client
+#[derive(Clone, Copy, ValueEnum)]
+pub enum VaultFormat {
+    Table,
+    Json,
+    Names,
+}
+
+/// What Glacier reports about a vault, as of its last inventory, next to the date of the inventory
+/// cached locally
+#[derive(Debug, Serialize)]
+pub struct VaultSummary {
+    pub vault_name: String,
+    pub vault_arn: String,
+    pub creation_date: String,
+    pub number_of_archives: i64,
+    pub size_in_bytes: i64,
+    pub last_inventory_date: Option<String>,
+    pub cached_inventory_date: Option<String>,
+}
+
+impl VaultSummary {
+    /// Glacier answers `list-vaults` and `describe-vault` with two types carrying the same fields
+    fn new(
+        vault_name: &str,
+        vault_arn: Option<&str>,
+        creation_date: Option<&str>,
+        number_of_archives: i64,
+        size_in_bytes: i64,
+        last_inventory_date: Option<&str>,
+    ) -> Result<VaultSummary, anyhow::Error> {
+        Ok(VaultSummary {
+            cached_inventory_date: indexed_inventory_date(&open_index()?, vault_name)?,
+            vault_name: String::from(vault_name),
+            vault_arn: String::from(vault_arn.unwrap_or_default()),
+            creation_date: String::from(creation_date.unwrap_or_default()),
+            number_of_archives,
+            size_in_bytes,
+            last_inventory_date: last_inventory_date.map(String::from),
+        })
+    }
+
+    fn from_listing(vault: &DescribeVaultOutput) -> Result<VaultSummary, anyhow::Error> {
+        VaultSummary::new(
+            vault.vault_name().unwrap_or_default(),
+            vault.vault_arn(),
+            vault.creation_date(),
+            vault.number_of_archives(),
+            vault.size_in_bytes(),
+            vault.last_inventory_date(),
+        )
+    }
+}
+
+pub async fn list_all_vaults(client: &Client) -> Result<Vec<DescribeVaultOutput>, anyhow::Error> {
+    let mut pages = client
         .list_vaults()
         .account_id("-")
-        .set_limit(Some(100))
-        .send()
-        .await;
-
-    match job {
-        Ok(success_listing) => {
-            match success_listing.vault_list {
-                Some(list) => {
-                    list.iter()
-                        .map(|x| x.vault_name())
-                        .filter_map(Option::Some)
-                        .map(|x| x.unwrap())
-                        .for_each(|x| println!("{}", x));
-                }
-                None => {
-                    println!("No vaults found for this account")
-                }
-            };
+        .into_paginator()
+        .items()
+        .send();
+    let mut vaults = vec![];
+    while let Some(vault) = pages.next().await {
+        vaults.push(vault?);
+    }
+    Ok(vaults)
+}
+
+fn print_vaults(vaults: &[VaultSummary], format: VaultFormat) -> Result<(), anyhow::Error> {
+    match format {
+        VaultFormat::Table => {
+            println!(
+                "{:<30} {:<20} {:>10} {:>10}  {:<20} CACHED INVENTORY",
+                "NAME", "CREATED", "ARCHIVES", "SIZE", "LAST INVENTORY"
+            );
+            for vault in vaults {
+                println!(
+                    "{:<30} {:<20} {:>10} {:>10}  {:<20} {}",
+                    vault.vault_name,
+                    vault.creation_date,
+                    vault.number_of_archives,
+                    human_size(vault.size_in_bytes),
+                    vault.last_inventory_date.as_deref().unwrap_or("-"),
+                    vault.cached_inventory_date.as_deref().unwrap_or("-")
+                );
+            }
+        }
+        VaultFormat::Json => println!("{}", serde_json::to_string_pretty(vaults)?),
+        VaultFormat::Names => vaults.iter().for_each(|x| println!("{}", x.vault_name)),
+    }
+    Ok(())
+}
+
+pub async fn do_listing(client: &Client, format: VaultFormat) -> Result<(), anyhow::Error> {
+    match list_all_vaults(client).await {
+        Ok(list) => {
+            if list.is_empty() {
+                eprintln!("No vaults found for this account")
+            }
+            let vaults = list
+                .iter()
+                .map(VaultSummary::from_listing)
+                .collect::<Result<Vec<_>, _>>()?;
+            print_vaults(&vaults, format)?;
         }
         Err(reason) => {
             println!("Listing of vaults failed! - {}", reason);
@@ -31,3 +120,69 @@ pub async fn do_listing(client: &Client) -> Result<(), anyhow::Error> {
 
     Ok(())
 }
+
+/// Describe a vault as Glacier reports it, and what the local index knows about it
+pub async fn do_describe_vault(
+    client: &Client,
+    vault_name: &str,
+    format: VaultFormat,
+) -> Result<(), anyhow::Error> {
+    let output = client
+        .describe_vault()
+        .account_id("-")
+        .vault_name(vault_name)
+        .send()
+        .await?;
+    let vault = VaultSummary::new(
+        output.vault_name().unwrap_or(vault_name),
+        output.vault_arn(),
+        output.creation_date(),
+        output.number_of_archives(),
+        output.size_in_bytes(),
+        output.last_inventory_date(),
+    )?;
+    match format {
+        VaultFormat::Json => println!("{}", serde_json::to_string_pretty(&vault)?),
+        VaultFormat::Names => println!("{}", vault.vault_name),
+        VaultFormat::Table => {
+            let field = |name: &str, value: String| println!("{:<20} {}", name.yellow(), value);
+            field("name", vault.vault_name.clone());
+            field("arn", vault.vault_arn.clone());
+            field("created", vault.creation_date.clone());
+            field("archives", vault.number_of_archives.to_string());
+            field(
+                "size",
+                format!(
+                    "{} ({} bytes)",
+                    human_size(vault.size_in_bytes),
+                    vault.size_in_bytes
+                ),
+            );
+            field(
+                "last inventory",
+                vault
+                    .last_inventory_date
+                    .clone()
+                    .unwrap_or_else(|| String::from("-")),
+            );
+            field(
+                "cached inventory",
+                describe_inventory_age(&cached_inventory_age(vault_name)?),
+            );
+            if vault.cached_inventory_date.is_some() {
+                let index = open_index()?;
+                let deleted = pending_deletions(&index, vault_name)?;
+                field(
+                    "cached archives",
+                    format!(
+                        "{}, {} uploaded since the inventory, {} deleted since the inventory",
+                        indexed_archive_count(&index, vault_name)? - deleted,
+                        pending_uploads(&index, vault_name)?,
+                        deleted
+                    ),
+                );
+            }
+        }
+    }
+    Ok(())
+}
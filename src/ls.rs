@@ -0,0 +1,90 @@
+use crate::index::{open_index, pending_deletions, query_archives};
+use crate::inventory::{cached_inventory_age, describe_inventory_age, warn_stale_inventory};
+use crate::shared::{human_size, ArchiveItem};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use regex::Regex;
+use std::io::stdout;
+
+#[derive(Clone, Copy, PartialEq, ValueEnum)]
+pub enum SortKey {
+    Date,
+    Size,
+    Description,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+    Ids,
+}
+
+#[derive(Default)]
+pub struct ArchiveFilters {
+    pub description: Option<Regex>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+}
+
+pub fn print_archives(archives: &[ArchiveItem], format: OutputFormat) -> Result<(), anyhow::Error> {
+    match format {
+        OutputFormat::Table => {
+            println!(
+                "{:<20} {:>10}  {:<8} {:<40} ARCHIVE ID",
+                "CREATED", "SIZE", "STATE", "DESCRIPTION"
+            );
+            for archive in archives {
+                println!(
+                    "{:<20} {:>10}  {:<8} {:<40} {}",
+                    archive.creation_date,
+                    human_size(archive.size),
+                    if archive.pending_upload {
+                        "uploaded"
+                    } else {
+                        ""
+                    },
+                    archive.archive_description,
+                    archive.archive_id
+                );
+            }
+        }
+        OutputFormat::Json => println!("{}", serde_json::to_string_pretty(archives)?),
+        OutputFormat::Csv => {
+            let mut writer = csv::Writer::from_writer(stdout());
+            for archive in archives {
+                writer.serialize(archive)?;
+            }
+            writer.flush()?;
+        }
+        OutputFormat::Ids => archives.iter().for_each(|x| println!("{}", x.archive_id)),
+    }
+    Ok(())
+}
+
+/// List the cached inventory of a vault without talking to Glacier
+pub fn do_ls(
+    vault_name: &str,
+    filters: &ArchiveFilters,
+    sort: SortKey,
+    reverse: bool,
+    format: OutputFormat,
+) -> Result<(), anyhow::Error> {
+    let mut index = open_index()?;
+    let archives = query_archives(&mut index, vault_name, filters, sort, reverse)?;
+    print_archives(&archives, format)?;
+    let inventory_age = cached_inventory_age(vault_name)?;
+    eprintln!("{}", describe_inventory_age(&inventory_age));
+    warn_stale_inventory(vault_name, &inventory_age);
+    let deleted = pending_deletions(&index, vault_name)?;
+    if deleted > 0 {
+        eprintln!(
+            "{} archives deleted since the last inventory are not listed",
+            deleted
+        );
+    }
+    Ok(())
+}
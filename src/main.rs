@@ -1,10 +1,18 @@
+mod browser;
+mod config;
 mod create;
 mod delete_archive;
+mod delete_vault;
 mod download;
+mod index;
 mod inventory;
+mod jobs;
+mod keymap;
 mod list_vaults;
+mod ls;
 mod multipart_upload;
 mod shared;
+mod theme;
 use aws_config::BehaviorVersion as version;
 use clap::{Parser, Subcommand};
 
@@ -25,9 +33,42 @@ enum Commands {
         description: String,
     },
     ///  Get the inventory of a particular vault
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
     Inventory {
-        #[arg(long, short)]
-        vault_name: String,
+        #[command(subcommand)]
+        command: Option<InventoryCommands>,
+        #[arg(long, short, required_unless_present_any = ["pending", "all"])]
+        /// Required if not finishing pending jobs nor taking the inventory of all vaults
+        vault_name: Option<String>,
+        /// Take the inventory of every vault of the account
+        #[arg(long, short, conflicts_with_all = ["vault_name", "pending"])]
+        all: bool,
+        #[arg(long, default_value_t = 4)]
+        /// Optional: How many inventories to download at the same time with --all
+        concurrency: usize,
+        #[arg(long, short, value_enum, default_value_t = inventory::InventoryFormat::Json)]
+        /// Optional: Format to request the inventory in - CSV inventories are also written out as
+        /// inventory.csv next to the local inventory
+        format: inventory::InventoryFormat,
+        /// Only initiate the inventory job and return, finish it later with --pending
+        #[arg(long, conflicts_with = "pending")]
+        no_wait: bool,
+        #[arg(long, value_parser = shared::parse_date, conflicts_with = "all")]
+        /// Optional: Only list archives created on or after this date, e.g. 2024-01-31
+        start_date: Option<chrono::DateTime<chrono::Utc>>,
+        #[arg(long, value_parser = shared::parse_date, conflicts_with = "all")]
+        /// Optional: Only list archives created before this date, e.g. 2024-12-31
+        end_date: Option<chrono::DateTime<chrono::Utc>>,
+        #[arg(long, conflicts_with = "all")]
+        /// Optional: How many archives each inventory job returns - following jobs are initiated
+        /// until the whole inventory has been retrieved
+        limit: Option<u32>,
+        #[arg(long, conflicts_with = "all")]
+        /// Optional: Resume an inventory from a marker returned by an earlier limited job
+        marker: Option<String>,
+        /// Pass this option to finish inventory jobs you started earlier
+        #[arg(long, short, conflicts_with = "vault_name")]
+        pending: bool,
     },
     ///  Download a job
     Download {
@@ -35,21 +76,145 @@ enum Commands {
         /// Required if not finishing pending jobs - you will be prompted to select an archive from
         /// a list. List will be empty if you have not queried for inventory first
         vault_name: Option<String>,
-        #[arg(long, short, default_value = None)]
-        /// Optional: Where to write out the archive to
+        #[arg(long, short, default_value = None, conflicts_with = "pending")]
+        /// Optional: Where to write out the archive to when a single archive is selected, `-` to
+        /// stream it to stdout
         output_as: Option<String>,
+        #[arg(long, default_value = ".")]
+        /// Optional: Directory to write archives to, named after their description, when no
+        /// --output-as is given
+        output_dir: String,
         /// Pass this option to finish a job you started earlier
-        #[arg(long, short, exclusive = true)]
+        #[arg(long, short, conflicts_with = "vault_name")]
         pending: bool,
+        #[arg(long, default_value_t = 4)]
+        /// Optional: How many archives to download at the same time when several are selected
+        concurrency: usize,
+        #[command(flatten)]
+        selectors: shared::ArchiveSelectors,
     },
 
     ///  Delete a particular archive by selecting it from an archive.
     DeleteArchive {
         #[arg(long, short)]
         vault_name: String,
+        #[command(flatten)]
+        selectors: shared::ArchiveSelectors,
+        /// Delete the selected archives without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Delete a vault, Glacier only deletes vaults that are empty as of their last inventory
+    DeleteVault {
+        #[arg(long, short)]
+        vault_name: String,
+        /// Delete every archive of the cached inventory first, run it again to resume or to
+        /// delete the vault once an inventory shows it empty
+        #[arg(long)]
+        empty: bool,
+        /// Delete without asking for confirmation
+        #[arg(long, short)]
+        yes: bool,
+    },
+    /// Describe a vault: its size, number of archives and last inventory
+    DescribeVault {
+        #[arg(long, short)]
+        vault_name: String,
+        #[arg(long, short, value_enum, default_value_t = list_vaults::VaultFormat::Table)]
+        format: list_vaults::VaultFormat,
+    },
+    /// List the names of all vaults, or their size, number of archives and last inventory
+    ListVaults {
+        #[arg(long, short, value_enum, default_value_t = list_vaults::VaultFormat::Names)]
+        format: list_vaults::VaultFormat,
+    },
+    /// List the archives of the cached inventory of a vault
+    Ls {
+        #[arg(long, short)]
+        vault_name: String,
+        #[arg(long, short)]
+        /// Optional: Only list archives whose description matches this regular expression
+        description: Option<regex::Regex>,
+        #[arg(long, value_parser = shared::parse_date)]
+        /// Optional: Only list archives created on or after this date, e.g. 2024-01-31
+        created_after: Option<chrono::DateTime<chrono::Utc>>,
+        #[arg(long, value_parser = shared::parse_date)]
+        /// Optional: Only list archives created before this date, e.g. 2024-12-31
+        created_before: Option<chrono::DateTime<chrono::Utc>>,
+        #[arg(long, value_parser = shared::parse_size)]
+        /// Optional: Only list archives of at least this size, e.g. 10M
+        min_size: Option<i64>,
+        #[arg(long, value_parser = shared::parse_size)]
+        /// Optional: Only list archives of at most this size, e.g. 1.5G
+        max_size: Option<i64>,
+        #[arg(long, short, value_enum, default_value_t = ls::SortKey::Date)]
+        sort: ls::SortKey,
+        #[arg(long, short)]
+        /// Reverse the sort order
+        reverse: bool,
+        #[arg(long, short, value_enum, default_value_t = ls::OutputFormat::Table)]
+        format: ls::OutputFormat,
+    },
+    /// Inspect and manage retrieval and inventory jobs
+    Jobs {
+        #[command(subcommand)]
+        command: JobsCommands,
+    },
+}
+
+#[derive(Subcommand)]
+enum InventoryCommands {
+    /// Report archives added, removed and changed between two inventory snapshots
+    Diff {
+        #[arg(long, short)]
+        vault_name: String,
+        #[arg(long, short)]
+        /// Optional: Snapshot to compare from - defaults to the one before --to
+        from: Option<String>,
+        #[arg(long, short)]
+        /// Optional: Snapshot to compare to - defaults to the most recent one
+        to: Option<String>,
+        #[arg(long, short, conflicts_with_all = ["from", "to"])]
+        /// List the snapshots of the vault instead
+        list: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum JobsCommands {
+    /// List local jobs alongside the jobs Glacier reports for the vaults of the account
+    List {
+        #[arg(long, short)]
+        /// Optional: Only list jobs for this vault - defaults to every vault of the account
+        vault_name: Option<String>,
+    },
+    /// Describe a particular job
+    Describe {
+        job_id: String,
+        #[arg(long, short)]
+        /// Optional: Required if the job is not in the local jobs file
+        vault_name: Option<String>,
+    },
+    /// Remove a job from the local jobs file
+    Forget { job_id: String },
+    /// Pick completed jobs started outside basmati, e.g. from the AWS console, and download their
+    /// output
+    Adopt {
+        #[arg(long, short)]
+        vault_name: String,
+        #[arg(long, default_value = ".")]
+        /// Optional: Directory to write retrieved archives to, named after their description
+        output_dir: String,
+    },
+    /// Live dashboard of the local jobs, completed jobs can be downloaded from it
+    Watch {
+        #[arg(long, default_value_t = 60)]
+        /// Optional: Seconds between polls of the job statuses
+        interval: u64,
+        #[arg(long, default_value = ".")]
+        /// Optional: Directory to write retrieved archives to when the job did not record one
+        output_dir: String,
     },
-    /// List vaults
-    ListVaults {},
 }
 
 #[derive(Parser)]
@@ -59,7 +224,6 @@ enum Commands {
     about,
     long_about = "Get inventory, upload/download/delete an archive and more"
 )]
-
 struct Cli {
     #[arg(short, long, action = clap::ArgAction::Count)]
     debug: u8,
@@ -72,6 +236,10 @@ struct Cli {
 async fn main() -> Result<(), anyhow::Error> {
     let config = aws_config::load_defaults(version::v2024_03_28()).await;
     let client = aws_sdk_glacier::Client::new(&config);
+    shared::install_terminal_hooks();
+    if config::read_config().is_ok_and(|x| theme::Theme::is_monochrome(&x)) {
+        colored::control::set_override(false);
+    }
 
     match &Cli::parse().command {
         Some(Commands::Create { vault_name }) => {
@@ -88,36 +256,145 @@ async fn main() -> Result<(), anyhow::Error> {
                 .expect("Operation Failed");
             Ok(())
         }
-        Some(Commands::Inventory { vault_name }) => {
-            inventory::do_inventory(&client, vault_name)
-                .await
-                .expect("Operation Failed");
+        Some(Commands::Inventory {
+            command:
+                Some(InventoryCommands::Diff {
+                    vault_name,
+                    from,
+                    to,
+                    list,
+                }),
+            ..
+        }) => {
+            inventory::do_diff(vault_name, from, to, list).expect("Operation Failed");
+            Ok(())
+        }
+        Some(Commands::Inventory {
+            command: None,
+            vault_name,
+            all,
+            concurrency,
+            format,
+            no_wait,
+            start_date,
+            end_date,
+            limit,
+            marker,
+            pending,
+        }) => {
+            let glacier_date = |x: &chrono::DateTime<chrono::Utc>| {
+                x.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+            };
+            let filters = inventory::InventoryFilters {
+                start_date: start_date.as_ref().map(glacier_date),
+                end_date: end_date.as_ref().map(glacier_date),
+                limit: *limit,
+                marker: marker.clone(),
+            };
+            if *all {
+                inventory::do_inventory_all(&client, format, no_wait, concurrency).await
+            } else {
+                inventory::do_inventory(&client, vault_name, &filters, format, no_wait, pending)
+                    .await
+            }
+            .expect("Operation Failed");
             Ok(())
         }
         Some(Commands::Download {
             vault_name,
             output_as,
+            output_dir,
             pending,
+            concurrency,
+            selectors,
         }) => {
-            download::do_download(&client, vault_name, output_as, pending)
+            download::do_download(
+                &client,
+                vault_name,
+                output_as,
+                output_dir,
+                pending,
+                concurrency,
+                selectors,
+            )
+            .await
+            .expect("Operation Failed");
+            Ok(())
+        }
+        Some(Commands::DeleteArchive {
+            vault_name,
+            selectors,
+            yes,
+        }) => {
+            delete_archive::do_deletion(&client, vault_name, selectors, *yes)
+                .await
+                .expect("Operation Failed");
+            Ok(())
+        }
+        Some(Commands::DeleteVault {
+            vault_name,
+            empty,
+            yes,
+        }) => {
+            delete_vault::do_delete_vault(&client, vault_name, *empty, *yes)
                 .await
                 .expect("Operation Failed");
             Ok(())
         }
-        Some(Commands::DeleteArchive { vault_name }) => {
-            delete_archive::do_deletion(&client, vault_name)
+        Some(Commands::DescribeVault { vault_name, format }) => {
+            list_vaults::do_describe_vault(&client, vault_name, *format)
                 .await
                 .expect("Operation Failed");
             Ok(())
         }
-        Some(Commands::ListVaults {}) => {
-            list_vaults::do_listing(&client)
+        Some(Commands::ListVaults { format }) => {
+            list_vaults::do_listing(&client, *format)
                 .await
                 .expect("Operation Failed");
             Ok(())
         }
+        Some(Commands::Ls {
+            vault_name,
+            description,
+            created_after,
+            created_before,
+            min_size,
+            max_size,
+            sort,
+            reverse,
+            format,
+        }) => {
+            let filters = ls::ArchiveFilters {
+                description: description.clone(),
+                created_after: *created_after,
+                created_before: *created_before,
+                min_size: *min_size,
+                max_size: *max_size,
+            };
+            ls::do_ls(vault_name, &filters, *sort, *reverse, *format).expect("Operation Failed");
+            Ok(())
+        }
+        Some(Commands::Jobs { command }) => {
+            match command {
+                JobsCommands::List { vault_name } => jobs::do_list(&client, vault_name).await,
+                JobsCommands::Describe { job_id, vault_name } => {
+                    jobs::do_describe(&client, job_id, vault_name).await
+                }
+                JobsCommands::Forget { job_id } => jobs::do_forget(job_id).await,
+                JobsCommands::Adopt {
+                    vault_name,
+                    output_dir,
+                } => jobs::do_adopt(&client, vault_name, output_dir).await,
+                JobsCommands::Watch {
+                    interval,
+                    output_dir,
+                } => jobs::do_watch(&client, *interval, output_dir).await,
+            }
+            .expect("Operation Failed");
+            Ok(())
+        }
         None => {
-            println!("Nothing to do, exiting");
+            browser::run(&client).await.expect("Operation Failed");
             Ok(())
         }
     }
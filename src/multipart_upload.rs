@@ -1,9 +1,13 @@
-use crate::shared::{basmati_directory, clean_splits, create_if_not_exists, InfiniteIndeces};
+use crate::index::{open_index, record_upload};
+use crate::shared::{
+    basmati_directory, clean_splits, create_if_not_exists, ArchiveItem, InfiniteIndeces,
+};
 use anyhow::Result;
 use aws_sdk_glacier::{
     operation::initiate_multipart_upload::InitiateMultipartUploadOutput, Client,
 };
 use aws_smithy_types::byte_stream::ByteStream;
+use chrono::{SecondsFormat, Utc};
 use colored::Colorize;
 use regex::Regex;
 use sha256::digest;
@@ -32,9 +36,7 @@ fn get_part_size(file: &File) -> Result<u64, anyhow::Error> {
     ];
 
     let file_size = file.metadata().unwrap().size();
-    let mut i = sizes.into_iter();
-
-    while let Some(x) = i.next() {
+    for x in sizes {
         if file_size > x && file_size / x < MAX_PART_AMOUNT {
             println!("Splitting the archive in chunks of {} bytes", x);
             return Ok(x);
@@ -70,7 +72,7 @@ async fn split_file(
         let chunks: Vec<String> = buffer[0..bytes_read]
             .chunks(ONE_MB)
             .to_owned()
-            .map(|x| digest(x))
+            .map(digest)
             .collect();
 
         if bytes_read == 0 {
@@ -142,7 +144,7 @@ async fn send_files(
                     .range(format!(
                         "bytes {}-{}/*",
                         index as u64 * chunk_size,
-                        (index as u64 * chunk_size as u64) + size - 1
+                        (index as u64 * chunk_size) + size - 1
                     ))
                     .upload_id(output.upload_id().unwrap())
                     .vault_name(vault_name)
@@ -172,7 +174,7 @@ pub async fn complete_multipart_upload(
     archive_size: &u64,
     sha256: String,
     client: &Client,
-) -> Result<(), aws_sdk_glacier::Error> {
+) -> Result<String, aws_sdk_glacier::Error> {
     let client = client
         .complete_multipart_upload()
         .account_id("-")
@@ -189,7 +191,7 @@ pub async fn complete_multipart_upload(
                 output.location().unwrap().yellow(),
                 output.checksum().unwrap().yellow()
             );
-            Ok(())
+            Ok(output.archive_id().unwrap_or_default().to_string())
         }
         Err(reason) => Err(reason)?,
     }
@@ -212,8 +214,8 @@ fn tree_hash(vec_sha: &VecDeque<String>) -> String {
             continue;
         }
         // queue is done
-        if queue.len() == 0 {
-            if pairs.len() == 0 {
+        if queue.is_empty() {
+            if pairs.is_empty() {
                 let next = inter.clone();
                 if next.len() == 1 {
                     break next[0].to_string();
@@ -231,7 +233,7 @@ fn tree_hash(vec_sha: &VecDeque<String>) -> String {
 
                     break digest(bytes);
                 }
-                if next.len() == 0 {
+                if next.is_empty() {
                     let result = pairs.clone();
                     break result[0].to_string();
                 }
@@ -252,16 +254,17 @@ fn tree_hash(vec_sha: &VecDeque<String>) -> String {
 
 pub async fn do_multipart_upload(
     client: &Client,
-    file_path: &String,
+    file_path: &str,
     vault_name: &String,
     description: &String,
 ) -> Result<()> {
-    match split_file(&file_path).await {
+    match split_file(file_path).await {
         Ok((archive_size, temp_dir, sha256_vec, chunk_size)) => {
             println!("Starting data upload");
+            let sha256_tree_hash = tree_hash(&VecDeque::from(sha256_vec));
             match send_files(
-                &client,
-                &vault_name,
+                client,
+                vault_name,
                 temp_dir.as_str(),
                 description,
                 chunk_size,
@@ -271,15 +274,32 @@ pub async fn do_multipart_upload(
                 Ok(glacier_output) => {
                     match complete_multipart_upload(
                         &glacier_output,
-                        &vault_name,
+                        vault_name,
                         &archive_size,
-                        tree_hash(&VecDeque::from(sha256_vec)),
-                        &client,
+                        sha256_tree_hash.clone(),
+                        client,
                     )
                     .await
                     {
-                        Ok(_output) => {
+                        Ok(archive_id) => {
                             println!("{}", "upload confirmed".green());
+                            let archive = ArchiveItem {
+                                archive_id,
+                                archive_description: description.clone(),
+                                creation_date: Utc::now()
+                                    .to_rfc3339_opts(SecondsFormat::Secs, true),
+                                size: archive_size as i64,
+                                sha256_tree_hash,
+                                pending_upload: true,
+                            };
+                            if let Err(reason) = open_index()
+                                .and_then(|index| record_upload(&index, vault_name, &archive))
+                            {
+                                eprintln!(
+                                    "failed to add the upload to the local index - {}",
+                                    reason
+                                );
+                            }
                             clean_splits(&temp_dir).await;
 
                             Ok(())
@@ -1,28 +1,51 @@
+use crate::config::read_config;
+use crate::index::{find_archive, open_index, query_archives, IndexedArchives};
+use crate::inventory::{cached_inventory_age, check_inventory_age, warn_stale_inventory};
+use crate::keymap::{Command, Keymap};
+use crate::ls::{ArchiveFilters, SortKey};
+use crate::theme::Theme;
 use anyhow::anyhow;
 use aws_sdk_glacier::operation::describe_job::builders::DescribeJobFluentBuilder;
 use aws_sdk_glacier::operation::describe_job::DescribeJobOutput;
 use aws_sdk_glacier::operation::initiate_job::InitiateJobOutput;
+use aws_sdk_glacier::types::ActionCode;
+use aws_sdk_glacier::Client;
+use clap::ValueEnum;
 use colored::Colorize;
-use std::fs::File;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 
 use aws_sdk_glacier::operation::get_job_output::builders::GetJobOutputFluentBuilder;
 use crossterm::terminal::size;
 use crossterm::ExecutableCommand;
 use crossterm::{
-    event::{self, Event, KeyCode},
+    cursor::Show,
+    event::{
+        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+        MouseButton, MouseEventKind,
+    },
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use home::home_dir;
 use ratatui::{prelude::*, widgets::*};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::io::Read;
-use std::io::{stdout, Write};
+use std::io::{stderr, Stderr, Write};
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
 use std::time;
 use std::{fs, thread};
+use tokio::signal::unix::{signal, SignalKind};
 
 pub const TWENTY_FOUR_HOURS: i64 = 86400;
-const SLEEP_DURATION: u64 = 60 * 60;
+pub const SLEEP_DURATION: u64 = 60 * 60;
 
 pub enum Status {
     Failed = 1,
@@ -30,13 +53,30 @@ pub enum Status {
     Pending = 3,
 }
 
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
 pub enum JobType {
     Inventory = 1,
     Retrieval = 2,
 }
 
-#[derive(Debug, Deserialize, Clone)]
+impl JobType {
+    pub fn from_action(action: Option<&ActionCode>) -> Option<JobType> {
+        match action {
+            Some(ActionCode::InventoryRetrieval) => Some(JobType::Inventory),
+            Some(ActionCode::ArchiveRetrieval) => Some(JobType::Retrieval),
+            _ => None,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            JobType::Inventory => "inventory",
+            JobType::Retrieval => "retrieval",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ArchiveItem {
     #[serde(rename = "ArchiveId")]
     pub archive_id: String,
@@ -45,52 +85,153 @@ pub struct ArchiveItem {
     #[serde(rename = "CreationDate")]
     pub creation_date: String,
     #[serde(rename = "Size")]
-    #[allow(dead_code)]
-    size: i64,
+    pub size: i64,
     #[serde(rename = "SHA256TreeHash")]
-    #[allow(dead_code)]
-    sha256_tree_hash: String,
+    pub sha256_tree_hash: String,
+    /// Uploaded with basmati but not listed by an inventory yet
+    #[serde(skip)]
+    pub pending_upload: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// An item that can be picked from a list in the TUI
+pub trait Selectable: Clone {
+    fn id(&self) -> &str;
+    fn label(&self) -> String;
+    /// Column names lined up with `label`
+    fn header() -> Option<String> {
+        None
+    }
+    /// Size in bytes, summed up over the selection when known
+    fn size(&self) -> Option<i64> {
+        None
+    }
+    /// Fields shown in the details pane for the highlighted item
+    fn details(&self) -> Vec<(&'static str, String)> {
+        vec![]
+    }
+    /// Text matched by `/` searches in the TUI
+    fn search_text(&self) -> String {
+        self.label()
+    }
+    /// Order two items by a sort key, items that can not be sorted keep their order
+    fn compare(&self, _other: &Self, _key: SortKey) -> Ordering {
+        Ordering::Equal
+    }
+}
+
+impl Selectable for ArchiveItem {
+    fn id(&self) -> &str {
+        &self.archive_id
+    }
+    fn label(&self) -> String {
+        let pending = if self.pending_upload {
+            " (uploaded)"
+        } else {
+            ""
+        };
+        format!(
+            "{:<20} {:>10}  {}{}",
+            self.creation_date,
+            human_size(self.size),
+            self.archive_description,
+            pending
+        )
+    }
+    fn header() -> Option<String> {
+        Some(format!(
+            "{:<20} {:>10}  {}",
+            "CREATED", "SIZE", "DESCRIPTION"
+        ))
+    }
+    fn size(&self) -> Option<i64> {
+        Some(self.size)
+    }
+    fn details(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("archive id", self.archive_id.clone()),
+            ("tree hash", self.sha256_tree_hash.clone()),
+            ("size", format!("{} bytes", self.size)),
+        ]
+    }
+    fn search_text(&self) -> String {
+        format!("{} {}", self.archive_description, self.archive_id)
+    }
+    fn compare(&self, other: &Self, key: SortKey) -> Ordering {
+        match key {
+            SortKey::Date => self.creation_date.cmp(&other.creation_date),
+            SortKey::Size => self.size.cmp(&other.size),
+            SortKey::Description => self
+                .archive_description
+                .to_lowercase()
+                .cmp(&other.archive_description.to_lowercase()),
+        }
+    }
+}
+
+/// An archive for the tests, with the fields they do not care about filled in
+#[cfg(test)]
+pub fn test_archive(id: &str, description: &str, creation_date: &str, size: i64) -> ArchiveItem {
+    ArchiveItem {
+        archive_id: String::from(id),
+        archive_description: String::from(description),
+        creation_date: String::from(creation_date),
+        size,
+        sha256_tree_hash: String::from("hash"),
+        pending_upload: false,
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InitiatedJob {
     pub location: String,
     pub job_id: String,
     pub vault: String,
     pub timestamp: i64,
     pub job_type: JobType,
+    /// Where the user asked for the output to be written to when the job was initiated
+    #[serde(default)]
+    pub output_path: Option<String>,
 }
 
-#[derive(Debug, Deserialize)]
-struct Vault<'a> {
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Vault {
     #[serde(rename = "VaultARN")]
-    #[allow(dead_code)]
-    vault_arn: &'a str,
+    pub vault_arn: String,
     #[serde(rename = "InventoryDate")]
-    #[allow(dead_code)]
-    inventory_date: &'a str,
+    pub inventory_date: String,
     #[serde(rename = "ArchiveList")]
-    archive_list: Vec<ArchiveItem>,
+    pub archive_list: Vec<ArchiveItem>,
 }
 
 #[derive(Clone)]
 pub struct Events<T: Clone> {
     items: Vec<T>,
+    /// Indices into `items` of the entries shown, narrowed down by a search
+    visible: Vec<usize>,
+    /// Key the shown entries are ordered by and whether the order is reversed
+    sort: Option<(SortKey, bool)>,
     state: ListState,
 }
 
-impl<T: std::clone::Clone> Events<T> {
-    pub fn new(items: Vec<T>) -> Events<T> {
-        Events {
-            items,
-            state: ListState::default().with_selected(Some(0)),
-        }
+/// Moving the highlight through a list, whether its rows are held in memory or paged in from the
+/// index
+pub trait Cursor {
+    fn state(&mut self) -> &mut ListState;
+    /// How many rows the list shows
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
-    pub fn next(&mut self) {
-        let i = match self.state.selected() {
+    fn next(&mut self) {
+        let len = self.len();
+        if len == 0 {
+            return;
+        }
+        let i = match self.state().selected() {
             Some(i) => {
-                if i >= self.items.len() - 1 {
+                if i >= len - 1 {
                     0
                 } else {
                     i + 1
@@ -98,28 +239,293 @@ impl<T: std::clone::Clone> Events<T> {
             }
             None => 0,
         };
-        self.state.select(Some(i));
+        self.state().select(Some(i));
     }
-    pub fn previous(&mut self) {
-        let i = match self.state.selected() {
+    fn previous(&mut self) {
+        let len = self.len();
+        if len == 0 {
+            return;
+        }
+        let i = match self.state().selected() {
             Some(i) => {
                 if i == 0 {
-                    self.items.len() - 1
+                    len - 1
                 } else {
                     i - 1
                 }
             }
             None => 0,
         };
-        self.state.select(Some(i));
+        self.state().select(Some(i));
+    }
+
+    /// Move the highlight by several rows at once, stopping at either end of the list
+    fn move_by(&mut self, rows: isize) {
+        if self.is_empty() {
+            return;
+        }
+        let current = self.state().selected().unwrap_or(0) as isize;
+        let last = self.len() as isize - 1;
+        self.state()
+            .select(Some((current + rows).clamp(0, last) as usize));
+    }
+
+    fn first(&mut self) {
+        self.state().select(Some(0));
+    }
+
+    fn last(&mut self) {
+        let last = self.len().saturating_sub(1);
+        self.state().select(Some(last));
+    }
+
+    /// Highlight the row of a bordered list drawn in `area` that was clicked, returns whether the
+    /// click landed on an item. Clicks on the border or outside of the list are ignored
+    fn click(&mut self, area: Rect, column: u16, row: u16) -> bool {
+        if row <= area.y || row + 1 >= area.y + area.height {
+            return false;
+        }
+        if column <= area.x || column + 1 >= area.x + area.width {
+            return false;
+        }
+        let index = self.state().offset() + (row - area.y - 1) as usize;
+        if index >= self.len() {
+            return false;
+        }
+        self.state().select(Some(index));
+        true
+    }
+}
+
+/// The items of the multi-select picker, held in memory or paged in from the index so that vaults
+/// with millions of archives are never loaded at once
+pub trait Listing<T>: Cursor {
+    /// How many items there are without a search
+    fn total(&self) -> usize;
+    /// The shown items from `offset` on, in the order they are shown
+    fn page(&mut self, offset: usize, limit: usize) -> Result<Vec<T>, anyhow::Error>;
+    /// Only show the items matching a search
+    fn search(&mut self, query: &str) -> Result<(), anyhow::Error>;
+    /// Order the shown items by a key, picking the key that is already in use reverses the order
+    fn sort(&mut self, key: SortKey) -> Result<(), anyhow::Error>;
+    fn sorted_by(&self) -> Option<(SortKey, bool)>;
+
+    fn highlighted_item(&mut self) -> Result<Option<T>, anyhow::Error> {
+        match self.state().selected() {
+            Some(i) => Ok(self.page(i, 1)?.pop()),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Scroll a list that is read a page at a time so that its highlighted row is among the `height`
+/// rows on screen. Returns the offset of the first row on screen and the state to draw the rows with
+pub fn scroll_page(state: &mut ListState, height: usize) -> (usize, ListState) {
+    let highlighted = state.selected().unwrap_or(0);
+    let offset = if highlighted < state.offset() {
+        highlighted
+    } else if highlighted >= state.offset() + height {
+        highlighted + 1 - height.max(1)
+    } else {
+        state.offset()
+    };
+    *state.offset_mut() = offset;
+    (
+        offset,
+        ListState::default().with_selected(Some(highlighted - offset)),
+    )
+}
+
+/// Flip the order when the key is already in use, start ascending otherwise
+pub fn next_sort(current: Option<(SortKey, bool)>, key: SortKey) -> Option<(SortKey, bool)> {
+    match current {
+        Some((current, reverse)) if current == key => Some((key, !reverse)),
+        _ => Some((key, false)),
+    }
+}
+
+pub fn sort_label(sort: Option<(SortKey, bool)>) -> Option<String> {
+    sort.map(|(key, reverse)| {
+        format!(
+            "sorted by {} {}",
+            key.to_possible_value()
+                .map_or(String::new(), |x| x.get_name().to_string()),
+            if reverse { "desc" } else { "asc" }
+        )
+    })
+}
+
+impl<T: std::clone::Clone> Events<T> {
+    pub fn new(items: Vec<T>) -> Events<T> {
+        Events {
+            visible: (0..items.len()).collect(),
+            sort: None,
+            items,
+            state: ListState::default().with_selected(Some(0)),
+        }
+    }
+
+    pub fn visible_items(&self) -> impl Iterator<Item = &T> {
+        self.visible.iter().map(|&i| &self.items[i])
     }
 
     pub fn choose(&mut self) -> Option<T> {
-        match self.state.selected() {
-            Some(i) => Some(self.items[i].clone()),
-            None => None,
+        self.state
+            .selected()
+            .and_then(|i| self.visible.get(i))
+            .map(|&i| self.items[i].clone())
+    }
+}
+
+impl<T: std::clone::Clone> Cursor for Events<T> {
+    fn state(&mut self) -> &mut ListState {
+        &mut self.state
+    }
+
+    fn len(&self) -> usize {
+        self.visible.len()
+    }
+}
+
+impl<T: Selectable> Events<T> {
+    /// Only show the items matching a fuzzy search, the highlighted item stays highlighted when it
+    /// still matches
+    pub fn filter(&mut self, query: &str) {
+        let highlighted = self.highlighted();
+        self.visible = (0..self.items.len())
+            .filter(|&i| fuzzy_match(query, &self.items[i].search_text()))
+            .collect();
+        self.apply_sort();
+        self.highlight(highlighted);
+    }
+
+    /// Order the shown items by a key, picking the key that is already in use reverses the order
+    pub fn sort_by(&mut self, key: SortKey) {
+        self.sort = next_sort(self.sort, key);
+        let highlighted = self.highlighted();
+        self.apply_sort();
+        self.highlight(highlighted);
+    }
+
+    fn apply_sort(&mut self) {
+        if let Some((key, reverse)) = self.sort {
+            let items = &self.items;
+            self.visible.sort_by(|&a, &b| {
+                let ordering = items[a].compare(&items[b], key);
+                if reverse {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
         }
     }
+
+    fn highlighted(&self) -> Option<usize> {
+        self.state
+            .selected()
+            .and_then(|i| self.visible.get(i))
+            .copied()
+    }
+
+    fn highlight(&mut self, item: Option<usize>) {
+        let position = item
+            .and_then(|x| self.visible.iter().position(|&y| y == x))
+            .unwrap_or(0);
+        self.state.select(Some(position));
+    }
+}
+
+impl<T: Selectable> Listing<T> for Events<T> {
+    fn total(&self) -> usize {
+        self.items.len()
+    }
+
+    fn page(&mut self, offset: usize, limit: usize) -> Result<Vec<T>, anyhow::Error> {
+        Ok(self
+            .visible_items()
+            .skip(offset)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    fn search(&mut self, query: &str) -> Result<(), anyhow::Error> {
+        self.filter(query);
+        Ok(())
+    }
+
+    fn sort(&mut self, key: SortKey) -> Result<(), anyhow::Error> {
+        self.sort_by(key);
+        Ok(())
+    }
+
+    fn sorted_by(&self) -> Option<(SortKey, bool)> {
+        self.sort
+    }
+}
+
+#[test]
+fn test_events_click_inside_the_list_only() {
+    let mut events = Events::new(vec![String::from("yes"), String::from("no")]);
+    let area = Rect::new(50, 0, 50, 10);
+    assert!(!events.click(area, 10, 2));
+    assert!(!events.click(area, 50, 2));
+    assert_eq!(events.choose().as_deref(), Some("yes"));
+    assert!(events.click(area, 55, 2));
+    assert_eq!(events.choose().as_deref(), Some("no"));
+    assert!(!events.click(area, 55, 3));
+}
+
+#[test]
+fn test_events_sort_and_filter() {
+    let archive = |id: &str, size: i64| {
+        test_archive(id, &format!("photos {}", id), "2024-01-31T10:20:30Z", size)
+    };
+    let mut events = Events::new(vec![archive("a", 2), archive("b", 3), archive("c", 1)]);
+    let ids = |events: &Events<ArchiveItem>| -> Vec<String> {
+        events
+            .visible_items()
+            .map(|x| x.archive_id.clone())
+            .collect()
+    };
+    events.sort_by(SortKey::Size);
+    assert_eq!(ids(&events), vec!["c", "a", "b"]);
+    events.sort_by(SortKey::Size);
+    assert_eq!(ids(&events), vec!["b", "a", "c"]);
+    events.last();
+    events.filter("c");
+    assert_eq!(ids(&events), vec!["c"]);
+    assert_eq!(
+        events.choose().map(|x| x.archive_id),
+        Some(String::from("c"))
+    );
+    events.filter("");
+    assert_eq!(ids(&events), vec!["b", "a", "c"]);
+    events.move_by(-10);
+    assert_eq!(
+        events.choose().map(|x| x.archive_id),
+        Some(String::from("b"))
+    );
+}
+
+/// Case insensitive match of every whitespace separated term of the query, the characters of a
+/// term have to appear in order but not next to each other
+pub fn fuzzy_match(query: &str, text: &str) -> bool {
+    let text = text.to_lowercase();
+    query.split_whitespace().all(|term| {
+        let mut chars = text.chars();
+        term.to_lowercase().chars().all(|x| chars.any(|y| y == x))
+    })
+}
+
+#[test]
+fn test_fuzzy_match() {
+    assert!(fuzzy_match("", "anything"));
+    assert!(fuzzy_match("phts 23", "Photos 2023 backup"));
+    assert!(fuzzy_match("BACK pho", "Photos 2023 backup"));
+    assert!(!fuzzy_match("stohp", "Photos 2023 backup"));
+    assert!(!fuzzy_match("photos 2024", "Photos 2023 backup"));
 }
 
 pub fn basmati_directory() -> String {
@@ -129,6 +535,120 @@ pub fn basmati_directory() -> String {
     }
 }
 
+/// Seconds elapsed since an ISO 8601 date as returned by Glacier, e.g. `2012-05-15T17:21:39.339Z`
+pub fn seconds_since(date: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(date)
+        .ok()
+        .map(|x| chrono::Utc::now().timestamp() - x.timestamp())
+}
+
+const SIZE_UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+
+pub fn human_size(bytes: i64) -> String {
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < SIZE_UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} {}", bytes, SIZE_UNITS[0])
+    } else {
+        format!("{:.1} {}", size, SIZE_UNITS[unit])
+    }
+}
+
+/// Parse a size given on the command line, either in bytes or with a binary suffix, e.g. `1.5G`
+pub fn parse_size(value: &str) -> Result<i64, String> {
+    let value = value.trim();
+    let split = value
+        .find(|x: char| x.is_ascii_alphabetic())
+        .unwrap_or(value.len());
+    let (number, suffix) = value.split_at(split);
+    let multiplier: i64 = match suffix
+        .to_ascii_uppercase()
+        .trim_end_matches("IB")
+        .trim_end_matches('B')
+    {
+        "" => 1,
+        "K" => 1 << 10,
+        "M" => 1 << 20,
+        "G" => 1 << 30,
+        "T" => 1 << 40,
+        _ => return Err(format!("{} is not a size like 512, 10M or 1.5G", value)),
+    };
+    number
+        .trim()
+        .parse::<f64>()
+        .map(|x| (x * multiplier as f64) as i64)
+        .map_err(|_| format!("{} is not a size like 512, 10M or 1.5G", value))
+}
+
+#[test]
+fn test_human_size() {
+    assert_eq!(human_size(512), "512 B");
+    assert_eq!(human_size(1536), "1.5 KiB");
+    assert_eq!(human_size(5 * 1024 * 1024 * 1024), "5.0 GiB");
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(parse_size("512"), Ok(512));
+    assert_eq!(parse_size("10M"), Ok(10 * 1024 * 1024));
+    assert_eq!(parse_size("1.5G"), Ok(1610612736));
+    assert_eq!(parse_size("2KiB"), Ok(2048));
+    assert!(parse_size("ten").is_err());
+}
+
+/// Parse a date given on the command line, either `2024-01-31` or a full RFC 3339 date
+pub fn parse_date(value: &str) -> Result<chrono::DateTime<chrono::Utc>, String> {
+    if let Ok(date) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Ok(date.with_timezone(&chrono::Utc));
+    }
+    chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map(|x| x.and_hms_opt(0, 0, 0).unwrap().and_utc())
+        .map_err(|_| format!("{} is not a date like 2024-01-31", value))
+}
+
+#[test]
+fn test_parse_date() {
+    assert_eq!(
+        parse_date("2024-01-31").unwrap().to_rfc3339(),
+        "2024-01-31T00:00:00+00:00"
+    );
+    assert_eq!(
+        parse_date("2024-01-31T10:20:30Z").unwrap().to_rfc3339(),
+        "2024-01-31T10:20:30+00:00"
+    );
+    assert!(parse_date("31/01/2024").is_err());
+}
+
+pub fn format_duration(seconds: i64) -> String {
+    let sign = if seconds < 0 { "-" } else { "" };
+    let seconds = seconds.abs();
+    let (days, hours, minutes) = (
+        seconds / 86400,
+        (seconds % 86400) / 3600,
+        (seconds % 3600) / 60,
+    );
+    if days > 0 {
+        format!("{}{}d {}h", sign, days, hours)
+    } else if hours > 0 {
+        format!("{}{}h {:02}m", sign, hours, minutes)
+    } else {
+        format!("{}{}m", sign, minutes)
+    }
+}
+
+#[test]
+fn test_format_duration() {
+    assert_eq!(format_duration(59), "0m");
+    assert_eq!(format_duration(61), "1m");
+    assert_eq!(format_duration(3600 * 2 + 60 * 5), "2h 05m");
+    assert_eq!(format_duration(TWENTY_FOUR_HOURS * 3 + 3600 * 4), "3d 4h");
+    assert_eq!(format_duration(-90), "-1m");
+}
+
 pub async fn delete_job_from_local(job_id: String) -> Result<(), anyhow::Error> {
     let jobs = get_jobs().await?;
     let jobs: Vec<InitiatedJob> = jobs.into_iter().filter(|x| x.job_id != job_id).collect();
@@ -148,28 +668,143 @@ pub async fn delete_expired_jobs_from_local() -> Result<(), anyhow::Error> {
     Ok(())
 }
 
-pub async fn get_job_output(
+/// Open the destination of a job output for writing, `-` meaning stdout
+pub async fn output_writer(
+    path: &str,
+) -> Result<Box<dyn AsyncWrite + Send + Unpin>, anyhow::Error> {
+    match path {
+        "-" => Ok(Box::new(tokio::io::stdout())),
+        _ => Ok(Box::new(tokio::fs::File::create(path).await?)),
+    }
+}
+
+/// Stream the output of a job into `writer`. Status messages go to stderr so that the output can
+/// be written to stdout and piped into another program
+pub async fn get_job_output<W: AsyncWrite + Unpin>(
     builder: GetJobOutputFluentBuilder,
-    mut file: File,
+    mut writer: W,
 ) -> Result<Status, anyhow::Error> {
     match builder.send().await {
         Ok(output) => {
-            let desc = String::from(output.archive_description().unwrap_or_else(|| "inventory"));
+            let desc = String::from(output.archive_description().unwrap_or("inventory"));
             let mut buffer = output.body;
-            println!("{}: {}", Colorize::green("downloading"), desc);
+            eprintln!("{}: {}", Colorize::green("downloading"), desc);
             while let Some(bytes) = buffer.try_next().await? {
-                file.write(&bytes)?;
+                writer.write_all(&bytes).await?;
             }
-            println!("{}: {}", Colorize::green("writing complete"), desc);
+            writer.flush().await?;
+            eprintln!("{}: {}", Colorize::green("writing complete"), desc);
             Ok(Status::Done)
         }
         Err(reason) => {
-            println!("failed to get inventory output: {}", reason);
+            eprintln!("failed to get job output: {}", reason);
             Ok(Status::Failed)
         }
     }
 }
 
+/// Turn an archive description into something that is safe to use as a file name
+pub fn filename_from_description(description: &str, fallback: &str) -> String {
+    let name: String = description
+        .trim()
+        .chars()
+        .map(|x| {
+            if x.is_alphanumeric() || matches!(x, '.' | '-' | '_') {
+                x
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim_start_matches('.');
+    if name.is_empty() {
+        String::from(fallback)
+    } else {
+        String::from(name)
+    }
+}
+
+#[test]
+fn test_filename_from_description() {
+    assert_eq!(
+        filename_from_description("photos 2023.tar.gz", "id"),
+        "photos_2023.tar.gz"
+    );
+    assert_eq!(
+        filename_from_description("../etc/passwd", "id"),
+        "_etc_passwd"
+    );
+    assert_eq!(filename_from_description("  ", "id"), "id");
+    assert_eq!(filename_from_description("..", "id"), "id");
+}
+
+/// Pick a path in `directory` for `filename` that neither exists yet nor is `reserved` by another
+/// download of the same session, e.g. `photos.tar.gz` becomes `photos-1.tar.gz`
+pub fn unique_path(directory: &Path, filename: &str, reserved: &HashSet<PathBuf>) -> PathBuf {
+    let (stem, extension) = match filename.find('.') {
+        Some(i) if i > 0 => filename.split_at(i),
+        _ => (filename, ""),
+    };
+    let mut candidate = directory.join(filename);
+    let mut i = 1;
+    while candidate.exists() || reserved.contains(&candidate) {
+        candidate = directory.join(format!("{}-{}{}", stem, i, extension));
+        i += 1;
+    }
+    candidate
+}
+
+#[test]
+fn test_unique_path() {
+    let directory = Path::new("/nonexistent-basmati-directory");
+    let mut reserved = HashSet::new();
+    let first = unique_path(directory, "photos.tar.gz", &reserved);
+    assert_eq!(first, directory.join("photos.tar.gz"));
+    reserved.insert(first);
+    let second = unique_path(directory, "photos.tar.gz", &reserved);
+    assert_eq!(second, directory.join("photos-1.tar.gz"));
+    reserved.insert(second);
+    assert_eq!(
+        unique_path(directory, "photos.tar.gz", &reserved),
+        directory.join("photos-2.tar.gz")
+    );
+}
+
+/// An absolute path in `output_dir` named after an archive description
+pub fn description_output_path(
+    description: &str,
+    fallback: &str,
+    output_dir: &str,
+    reserved: &HashSet<PathBuf>,
+) -> Result<String, anyhow::Error> {
+    let filename = filename_from_description(description, fallback);
+    fs::create_dir_all(output_dir)?;
+    let path = unique_path(Path::new(output_dir), &filename, reserved);
+    Ok(std::path::absolute(path)?.display().to_string())
+}
+
+/// Where a retrieved archive is written to: named after its description in `output_dir`,
+/// falling back to the job id when the archive is not in the cached inventory
+pub fn retrieval_output_path(
+    vault: &str,
+    archive_id: Option<&str>,
+    job_id: &str,
+    output_dir: &str,
+    reserved: &HashSet<PathBuf>,
+) -> Result<String, anyhow::Error> {
+    let description = archive_id.and_then(|id| {
+        find_archive(&mut open_index().ok()?, vault, id)
+            .ok()?
+            .map(|x| x.archive_description)
+    });
+    description_output_path(
+        &description.unwrap_or_default(),
+        job_id,
+        output_dir,
+        reserved,
+    )
+}
+
 pub async fn describe_job_loop(
     builder: DescribeJobFluentBuilder,
 ) -> Result<DescribeJobOutput, anyhow::Error> {
@@ -179,20 +814,164 @@ pub async fn describe_job_loop(
                 break Ok(output.unwrap());
             }
             Ok((Status::Pending, _)) => {
-                println!(
+                eprintln!(
                     "job is not ready - going to sleep and will try again in {} minutes",
                     SLEEP_DURATION / 60
                 );
                 thread::sleep(Duration::from_secs(SLEEP_DURATION))
             }
             _ => {
-                println!("describe_job failed");
+                eprintln!("describe_job failed");
                 break Err(anyhow!("describe job failed!"));
             }
         }
     }
 }
 
+/// How many times in a row describing a job may fail before the job is given up on
+const DESCRIBE_ATTEMPTS: u32 = 3;
+
+/// A job that is polled until it completes
+pub trait InFlight {
+    fn vault(&self) -> &str;
+    fn job_id(&self) -> &str;
+}
+
+/// A retrieval job in flight together with where its output should be written to
+pub struct PendingJob {
+    pub vault: String,
+    pub job_id: String,
+    pub output_path: String,
+}
+
+impl InFlight for PendingJob {
+    fn vault(&self) -> &str {
+        &self.vault
+    }
+    fn job_id(&self) -> &str {
+        &self.job_id
+    }
+}
+
+/// Jobs of a poll round, by whether they completed, are still running or could not be described
+/// `DESCRIBE_ATTEMPTS` times in a row
+pub struct Partitioned<J> {
+    pub completed: Vec<J>,
+    pub still_pending: Vec<J>,
+    pub failed: Vec<J>,
+}
+
+/// Describe every job once, splitting them into the ones that have completed and the ones that are
+/// still running. Jobs that can not be described stay pending, `describe_failures` counts the
+/// failures of each job across rounds
+pub async fn partition_completed<J: InFlight>(
+    client: &Client,
+    jobs: Vec<J>,
+    describe_failures: &mut HashMap<String, u32>,
+) -> Partitioned<J> {
+    let mut partitioned = Partitioned {
+        completed: vec![],
+        still_pending: vec![],
+        failed: vec![],
+    };
+    for job in jobs {
+        let describe_builder = client
+            .describe_job()
+            .account_id("-")
+            .vault_name(job.vault())
+            .job_id(job.job_id());
+        match describe_job_output(&describe_builder).await {
+            Ok((Status::Done, _)) => partitioned.completed.push(job),
+            Ok(_) => {
+                describe_failures.remove(job.job_id());
+                partitioned.still_pending.push(job)
+            }
+            Err(reason) => {
+                let failures = describe_failures
+                    .entry(job.job_id().to_string())
+                    .or_default();
+                *failures += 1;
+                eprintln!(
+                    "describe_job failed for job {} ({} of {} attempts): {}",
+                    job.job_id(),
+                    failures,
+                    DESCRIBE_ATTEMPTS,
+                    reason
+                );
+                if *failures >= DESCRIBE_ATTEMPTS {
+                    partitioned.failed.push(job);
+                } else {
+                    partitioned.still_pending.push(job);
+                }
+            }
+        }
+    }
+    partitioned
+}
+
+pub async fn sleep_unless_done(pending: usize) {
+    if pending > 0 {
+        eprintln!(
+            "{} jobs are not ready - going to sleep and will try again in {} minutes",
+            pending,
+            SLEEP_DURATION / 60
+        );
+        tokio::time::sleep(Duration::from_secs(SLEEP_DURATION)).await;
+    }
+}
+
+/// Poll jobs together until every one of them has completed, downloading the output of each job
+/// as soon as it is ready with at most `concurrency` downloads running at the same time. Jobs that
+/// were downloaded are removed from the local jobs file
+pub async fn download_when_ready(
+    client: &Client,
+    mut pending: Vec<PendingJob>,
+    concurrency: usize,
+) -> Result<(), anyhow::Error> {
+    let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut downloads = JoinSet::new();
+    let mut describe_failures = HashMap::new();
+    let mut failures = 0;
+    while !pending.is_empty() {
+        let partitioned = partition_completed(client, pending, &mut describe_failures).await;
+        failures += partitioned.failed.len();
+        for job in partitioned.completed {
+            let builder = client
+                .get_job_output()
+                .account_id("-")
+                .vault_name(&job.vault)
+                .job_id(&job.job_id);
+            let semaphore = semaphore.clone();
+            downloads.spawn(async move {
+                let result = async {
+                    let _permit = semaphore.acquire_owned().await?;
+                    let file = output_writer(&job.output_path).await?;
+                    get_job_output(builder, file).await
+                }
+                .await;
+                (job.job_id, result)
+            });
+        }
+        pending = partitioned.still_pending;
+        sleep_unless_done(pending.len()).await;
+    }
+
+    while let Some(result) = downloads.join_next().await {
+        match result {
+            Ok((job_id, Ok(Status::Done))) => delete_job_from_local(job_id).await?,
+            Ok((job_id, Err(reason))) => {
+                eprintln!("failed to get output of job {}, {:?}", job_id, reason);
+                failures += 1;
+            }
+            _ => failures += 1,
+        }
+    }
+    if failures > 0 {
+        return Err(anyhow!("{} jobs failed to download", failures));
+    }
+    Ok(())
+}
+
 pub async fn describe_job_output(
     builder: &DescribeJobFluentBuilder,
 ) -> Result<(Status, Option<DescribeJobOutput>), anyhow::Error> {
@@ -242,6 +1021,7 @@ pub async fn job_writer(bytes: Vec<u8>) -> Result<(), anyhow::Error> {
 pub async fn save_job_output(
     init_job_output: InitiateJobOutput,
     job_type: JobType,
+    output_path: Option<String>,
 ) -> Result<(), anyhow::Error> {
     let location = String::from(init_job_output.location().unwrap());
     let job_id = String::from(init_job_output.job_id().unwrap());
@@ -257,6 +1037,7 @@ pub async fn save_job_output(
         vault: vault.clone(),
         timestamp,
         job_type,
+        output_path,
     };
 
     let mut jobs = get_jobs().await.unwrap();
@@ -267,7 +1048,7 @@ pub async fn save_job_output(
 }
 
 pub async fn create_if_not_exists(path: &str) {
-    if let Err(err) = fs::create_dir_all(&path) {
+    if let Err(err) = fs::create_dir_all(path) {
         println!("{:?}", err);
         clean_splits(path).await
     }
@@ -287,31 +1068,185 @@ pub async fn clean_splits(temp_dir: &str) {
     }
 }
 
-pub async fn get_archive_from_tui(vault_name: &String) -> Result<Vec<ArchiveItem>, anyhow::Error> {
-    let mut file_handle = fs::File::open(format!(
-        "{}/vault/{}/inventory.json",
-        basmati_directory(),
-        &vault_name
-    ))
-    .expect(
-        "Failed to read the inventory file - have you pulled down the inventory of the vault yet?",
-    );
-    let mut json_data = String::new();
-    file_handle
-        .read_to_string(&mut json_data)
-        .expect("IO error reading the file");
+/// All archives of a vault known to the local index
+pub fn read_inventory(vault_name: &str) -> Result<Vec<ArchiveItem>, anyhow::Error> {
+    query_archives(
+        &mut open_index()?,
+        vault_name,
+        &Default::default(),
+        SortKey::Date,
+        false,
+    )
+}
+
+pub async fn get_archive_from_tui(
+    client: &Client,
+    vault_name: &str,
+) -> Result<Vec<ArchiveItem>, anyhow::Error> {
+    let archives = IndexedArchives::new(open_index()?, vault_name)?;
+    let inventory_age = check_inventory_age(client, vault_name).await?;
+
+    select_multiple_archives(archives, &inventory_age)
+}
 
-    let inventory: Vault = serde_json::from_str(&json_data).expect("error parsing JSON");
-    let items = inventory.archive_list.into_iter().collect::<Vec<_>>();
-    let events = Events::<ArchiveItem>::new(items);
+/// Flags picking archives from the cached inventory instead of the archive picker, so that
+/// downloads and deletions can run from scripts and cron jobs
+#[derive(clap::Args, Debug, Default)]
+pub struct ArchiveSelectors {
+    #[arg(long = "archive-id")]
+    /// Optional: Select the archive with this id instead of picking archives from a list, can be
+    /// repeated
+    pub archive_ids: Vec<String>,
+    #[arg(long)]
+    /// Optional: Select the archives whose whole description is exactly this text
+    pub description: Option<String>,
+    #[arg(long, conflicts_with = "description", value_parser = whole_description_regex)]
+    /// Optional: Select the archives whose whole description matches this regular expression, e.g.
+    /// `photos-2023-.*`
+    pub description_regex: Option<Regex>,
+    #[arg(long, value_parser = parse_date)]
+    /// Optional: Select the archives created on or after this date, e.g. 2024-01-31
+    pub created_after: Option<chrono::DateTime<chrono::Utc>>,
+    #[arg(long, value_parser = parse_date)]
+    /// Optional: Select the archives created before this date, e.g. 2024-12-31
+    pub created_before: Option<chrono::DateTime<chrono::Utc>>,
+    #[arg(long)]
+    /// Optional: Select the archives whose ids are listed in this file, one per line, `-` to read
+    /// them from stdin
+    pub from_file: Option<String>,
+}
+
+impl ArchiveSelectors {
+    fn is_empty(&self) -> bool {
+        self.archive_ids.is_empty()
+            && self.description.is_none()
+            && self.description_regex.is_none()
+            && self.created_after.is_none()
+            && self.created_before.is_none()
+            && self.from_file.is_none()
+    }
+
+    /// Ids given with --archive-id and listed in --from-file, skipping blank lines and `#` comments
+    fn ids(&self) -> Result<Vec<String>, anyhow::Error> {
+        let mut ids = self.archive_ids.clone();
+        if let Some(path) = &self.from_file {
+            let contents = if path == "-" {
+                std::io::read_to_string(std::io::stdin())?
+            } else {
+                fs::read_to_string(path)
+                    .map_err(|reason| anyhow!("Failed to read {} - {}", path, reason))?
+            };
+            ids.extend(
+                contents
+                    .lines()
+                    .map(str::trim)
+                    .filter(|x| !x.is_empty() && !x.starts_with('#'))
+                    .map(String::from),
+            );
+        }
+        Ok(ids)
+    }
+
+    /// --description as a regular expression matching only that exact text, or --description-regex
+    fn description_filter(&self) -> Result<Option<Regex>, anyhow::Error> {
+        match &self.description {
+            Some(text) => Ok(Some(Regex::new(&format!("^{}$", regex::escape(text)))?)),
+            None => Ok(self.description_regex.clone()),
+        }
+    }
+}
 
-    select_multiple_archives(events)
+/// A regular expression that has to match the whole description, not just part of it
+fn whole_description_regex(value: &str) -> Result<Regex, String> {
+    Regex::new(&format!("^(?:{})$", value)).map_err(|reason| reason.to_string())
+}
+
+#[test]
+fn test_description_filter() {
+    let filter = |description: Option<&str>, pattern: Option<&str>| {
+        ArchiveSelectors {
+            description: description.map(String::from),
+            description_regex: pattern.map(|x| whole_description_regex(x).unwrap()),
+            ..Default::default()
+        }
+        .description_filter()
+        .unwrap()
+        .unwrap()
+    };
+    let exact = filter(Some("backup.2023"), None);
+    assert!(exact.is_match("backup.2023"));
+    assert!(!exact.is_match("backupX2023"));
+    let exact = filter(Some("photos (1)"), None);
+    assert!(exact.is_match("photos (1)"));
+    assert!(!exact.is_match("photos 1"));
+    let pattern = filter(None, Some("photos-20(23|24)-.*"));
+    assert!(pattern.is_match("photos-2023-01"));
+    assert!(!pattern.is_match("backup photos-2023-01"));
+    assert!(whole_description_regex("backup [old").is_err());
+}
+
+/// The archives of the cached inventory matching every selector that was given
+fn select_archives_by_flags(
+    vault_name: &str,
+    selectors: &ArchiveSelectors,
+) -> Result<Vec<ArchiveItem>, anyhow::Error> {
+    let ids = selectors.ids()?;
+    let filters = ArchiveFilters {
+        description: selectors.description_filter()?,
+        created_after: selectors.created_after,
+        created_before: selectors.created_before,
+        ..Default::default()
+    };
+    let archives = query_archives(
+        &mut open_index()?,
+        vault_name,
+        &filters,
+        SortKey::Date,
+        false,
+    )?;
+    if let Some(missing) = ids
+        .iter()
+        .find(|id| !archives.iter().any(|x| &x.archive_id == *id))
+    {
+        return Err(anyhow!(
+            "Archive {} is not in the cached inventory of vault {} or does not match the other selectors",
+            missing,
+            vault_name
+        ));
+    }
+    let selected: Vec<ArchiveItem> = archives
+        .into_iter()
+        .filter(|x| ids.is_empty() || ids.contains(&x.archive_id))
+        .collect();
+    if selected.is_empty() {
+        return Err(anyhow!(
+            "No archive of vault {} matches the selectors",
+            vault_name
+        ));
+    }
+    Ok(selected)
+}
+
+/// Pick archives with the archive picker, or from the selector flags when any was given
+pub async fn select_archives(
+    client: &Client,
+    vault_name: &str,
+    selectors: &ArchiveSelectors,
+) -> Result<Vec<ArchiveItem>, anyhow::Error> {
+    if selectors.is_empty() {
+        return get_archive_from_tui(client, vault_name).await;
+    }
+    warn_stale_inventory(vault_name, &cached_inventory_age(vault_name)?);
+    select_archives_by_flags(vault_name, selectors)
 }
 
 pub fn confirm(title: String, confirmation_items: Vec<String>) -> Result<bool, anyhow::Error> {
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let config = read_config()?;
+    let keys = Keymap::new(&config);
+    let theme = Theme::new(&config)?;
+    let mut terminal = enter_terminal()?;
+    terminal.capture_mouse()?;
+    let mut list_area = Rect::default();
     let mut should_quit = false;
     let mut return_value = None;
     let confrim_options = vec![String::from("yes"), String::from("no")];
@@ -328,194 +1263,537 @@ pub fn confirm(title: String, confirmation_items: Vec<String>) -> Result<bool, a
                 .split(Rect::new(0, 0, terminal_width, terminal_height));
             let block = Block::default()
                 .title(title.clone())
-                .green()
+                .style(theme.border)
                 .borders(Borders::ALL);
             let text: Vec<Line> = confirmation_items
                 .clone()
                 .into_iter()
-                .map(|x| {
-                    Line::from(Span::styled(
-                        String::from(x),
-                        Style::default().fg(Color::Red),
-                    ))
-                })
+                .map(|x| Line::from(Span::styled(x, theme.warning)))
                 .collect();
             let display_text = Paragraph::new(text).block(Block::default());
 
             let list = List::new(list_items)
                 .bold()
-                .red()
+                .style(theme.warning)
                 .block(block)
                 .highlight_style(Style::new().italic())
                 .highlight_symbol("->")
                 .repeat_highlight_symbol(true);
 
             frame.render_widget(display_text, areas[0]);
+            list_area = areas[1];
             frame.render_stateful_widget(list, areas[1], &mut events.state);
         })?;
         if event::poll(time::Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Char('q') {
-                    should_quit = true;
-                }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Char('j') {
-                    events.next();
-                }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Char('k') {
-                    events.previous();
-                }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Down {
-                    events.next();
-                }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Up {
-                    events.previous();
-                }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Esc {
-                    should_quit = true;
+            let mut answered = false;
+            match event::read()? {
+                Event::Key(key) => {
+                    if is_interrupt(&key) {
+                        abort();
+                    }
+                    if key.kind != event::KeyEventKind::Press {
+                        continue;
+                    }
+                    match keys.command(
+                        &key,
+                        &[Command::Up, Command::Down, Command::Quit, Command::Confirm],
+                    ) {
+                        Some(Command::Up) => events.previous(),
+                        Some(Command::Down) => events.next(),
+                        Some(Command::Quit) => should_quit = true,
+                        Some(Command::Confirm) => answered = true,
+                        _ => {}
+                    }
                 }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Enter {
-                    match events.choose() {
-                        Some(value) => {
-                            should_quit = true;
-                            match value.as_str() {
-                                "yes" => {
-                                    return_value = Some(true);
-                                }
-                                "no" => {
-                                    return_value = Some(false);
-                                }
-                                _ => panic!("unexpected selection"),
+                Event::Mouse(mouse) => match mouse.kind {
+                    MouseEventKind::ScrollDown => events.next(),
+                    MouseEventKind::ScrollUp => events.previous(),
+                    // clicking yes or no answers right away
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        answered = events.click(list_area, mouse.column, mouse.row)
+                    }
+                    _ => {}
+                },
+                _ => {}
+            }
+            if answered {
+                match events.choose() {
+                    Some(value) => {
+                        should_quit = true;
+                        match value.as_str() {
+                            "yes" => {
+                                return_value = Some(true);
                             }
+                            "no" => {
+                                return_value = Some(false);
+                            }
+                            _ => panic!("unexpected selection"),
                         }
-                        None => {
-                            should_quit = true;
-                            println!("could not match user input")
-                        }
+                    }
+                    None => {
+                        should_quit = true;
+                        println!("could not match user input")
                     }
                 }
             }
         }
     }
-    release_terminal().expect("Issue releasing terminal");
-    Ok(return_value.unwrap())
+    drop(terminal);
+    // leaving without an answer is a no
+    Ok(return_value.unwrap_or(false))
 }
 
 pub fn select_multiple_archives(
-    mut events: Events<ArchiveItem>,
+    archives: IndexedArchives,
+    inventory_age: &str,
 ) -> Result<Vec<ArchiveItem>, anyhow::Error> {
-    enable_raw_mode()?;
-    stdout().execute(EnterAlternateScreen)?;
-    let mut terminal = Terminal::new(CrosstermBackend::new(stdout()))?;
+    let keys = Keymap::new(&read_config()?);
+    let archives = select_multiple(
+        archives,
+        &format!(
+            "Archives | {} | {} to select {} to confirm selection",
+            inventory_age,
+            keys.label(Command::Toggle),
+            keys.label(Command::Confirm)
+        ),
+    )?;
+    if !archives.is_empty() {
+        return Ok(archives);
+    }
+    Err(anyhow!("You must select an archive!"))
+}
+
+/// The items picked from a list in the order they were picked, looked up by id so that drawing or
+/// selecting the rows of a long list never scans the whole selection
+pub struct Selection<T> {
+    items: Vec<T>,
+    ids: HashSet<String>,
+    /// Summed up over the items that know their size, `None` while none of them does
+    size: Option<i64>,
+}
+
+impl<T> Default for Selection<T> {
+    fn default() -> Self {
+        Selection {
+            items: vec![],
+            ids: HashSet::new(),
+            size: None,
+        }
+    }
+}
+
+impl<T: Selectable> Selection<T> {
+    pub fn contains(&self, item: &T) -> bool {
+        self.ids.contains(item.id())
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn size(&self) -> Option<i64> {
+        self.size
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn into_items(self) -> Vec<T> {
+        self.items
+    }
+
+    pub fn clear(&mut self) {
+        *self = Selection::default();
+    }
+
+    fn add(&mut self, item: T) {
+        if self.ids.insert(item.id().to_string()) {
+            if let Some(size) = item.size() {
+                self.size = Some(self.size.unwrap_or(0) + size);
+            }
+            self.items.push(item);
+        }
+    }
+
+    pub fn toggle(&mut self, item: T) {
+        self.invert(vec![item]);
+    }
+
+    /// Select the items, keeping the ones already selected
+    pub fn select_all(&mut self, items: Vec<T>) {
+        items.into_iter().for_each(|x| self.add(x));
+    }
+
+    /// Unselect the selected items and select the others
+    pub fn invert(&mut self, items: Vec<T>) {
+        let mut dropped = HashSet::new();
+        for item in items {
+            if self.contains(&item) {
+                dropped.insert(item.id().to_string());
+            } else {
+                self.add(item);
+            }
+        }
+        if !dropped.is_empty() {
+            self.ids.retain(|x| !dropped.contains(x));
+            self.items.retain(|x| !dropped.contains(x.id()));
+            self.size = self
+                .items
+                .iter()
+                .filter_map(|x| x.size())
+                .reduce(|a, b| a + b);
+        }
+    }
+}
+
+#[test]
+fn test_selection_toggle_and_invert() {
+    let archive = |id: &str, size: i64| test_archive(id, id, "2024-01-31T10:20:30Z", size);
+    let ids = |selection: &Selection<ArchiveItem>| -> Vec<String> {
+        selection
+            .items()
+            .iter()
+            .map(|x| x.archive_id.clone())
+            .collect()
+    };
+    let mut selection = Selection::default();
+    selection.toggle(archive("b", 2));
+    selection.select_all(vec![archive("a", 1), archive("b", 2)]);
+    assert_eq!(ids(&selection), vec!["b", "a"]);
+    assert_eq!(selection.size(), Some(3));
+    selection.invert(vec![archive("a", 1), archive("c", 4)]);
+    assert_eq!(ids(&selection), vec!["b", "c"]);
+    assert_eq!(selection.size(), Some(6));
+    selection.toggle(archive("b", 2));
+    selection.toggle(archive("c", 4));
+    assert!(selection.is_empty());
+    assert_eq!(selection.size(), None);
+}
+
+pub fn select_multiple<T: Selectable>(
+    mut events: impl Listing<T>,
+    title: &str,
+) -> Result<Vec<T>, anyhow::Error> {
+    let config = read_config()?;
+    let keys = Keymap::new(&config);
+    let theme = Theme::new(&config)?;
+    let mut terminal = enter_terminal()?;
     let mut should_quit = false;
-    let mut return_values: Vec<ArchiveItem> = vec![];
+    let mut return_values = Selection::default();
+    let mut query = String::new();
+    let mut searching = false;
+    let mut page = 1;
+    let mut list_area = Rect::default();
+    terminal.capture_mouse()?;
     while !should_quit {
+        // the height of the list is only known once drawn, enough rows to fill the terminal are read
+        let (offset, mut window) = scroll_page(events.state(), page as usize);
+        let rows = events.page(offset, terminal.size()?.height as usize)?;
+        let details = events
+            .highlighted_item()?
+            .map(|x| x.details())
+            .unwrap_or_default();
         terminal.draw(|frame| {
-            let area = frame.size();
-            let list_items: Vec<ListItem> = events
-                .items
+            let header = T::header();
+            let areas = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Length(if header.is_some() { 1 } else { 0 }),
+                    Constraint::Min(3),
+                    Constraint::Length(if details.is_empty() {
+                        0
+                    } else {
+                        details.len() as u16 + 2
+                    }),
+                ])
+                .split(frame.size());
+            let list_items: Vec<ListItem> = rows
                 .iter()
                 .map(|x| {
-                    if return_values
-                        .clone()
-                        .iter()
-                        .map(|y| y.archive_id.as_str())
-                        .collect::<Vec<&str>>()
-                        .contains(&x.archive_id.as_str())
-                    {
-                        ListItem::new(Span::styled(
-                            format!("{} {} {}", x.archive_description, x.archive_id, " *"),
-                            Style::default().fg(Color::Green),
-                        ))
+                    if return_values.contains(x) {
+                        ListItem::new(Span::styled(format!("[x] {}", x.label()), theme.selected))
                     } else {
-                        ListItem::new(Span::styled(
-                            format!("{} {}", x.archive_description.clone(), x.archive_id.clone()),
-                            Style::default().fg(Color::White),
-                        ))
+                        ListItem::new(Span::styled(format!("[ ] {}", x.label()), theme.text))
                     }
                 })
                 .collect();
 
+            let selection = match return_values.size() {
+                Some(size) => format!("{} selected, {}", return_values.len(), human_size(size)),
+                None => format!("{} selected", return_values.len()),
+            };
+            let search = if searching || !query.is_empty() {
+                format!(
+                    "/{}{} | {} of {} ",
+                    query,
+                    if searching { "_" } else { "" },
+                    events.len(),
+                    events.total()
+                )
+            } else {
+                format!("{} to search ", keys.label(Command::Search))
+            };
+            let search = match sort_label(events.sorted_by()) {
+                Some(sort) => format!("{}| {} ", search, sort),
+                None => search,
+            };
             let block = Block::default()
-                .title("Archives | <Space> to select <Enter> to confirm selection")
-                .green()
+                .title(title)
+                .title(block::Title::from(search).position(block::Position::Bottom))
+                .title(
+                    block::Title::from(selection)
+                        .position(block::Position::Bottom)
+                        .alignment(Alignment::Right),
+                )
+                .style(theme.border)
                 .borders(Borders::ALL);
 
+            if let Some(header) = header {
+                // line the header up with the labels, past the border, the highlight symbol and
+                // the checkbox
+                frame.render_widget(
+                    Paragraph::new(format!("       {}", header))
+                        .bold()
+                        .style(theme.accent),
+                    areas[0],
+                );
+            }
+
+            let lines: Vec<Line> = details
+                .into_iter()
+                .map(|(name, value)| {
+                    Line::from(vec![
+                        Span::styled(format!("{:<12}", name), theme.accent),
+                        Span::raw(value),
+                    ])
+                })
+                .collect();
+            frame.render_widget(
+                Paragraph::new(lines)
+                    .block(Block::default().title("Details").borders(Borders::ALL)),
+                areas[2],
+            );
+
             let list = List::new(list_items)
                 .bold()
-                // .green()
                 .block(block)
                 .highlight_style(Style::new().italic())
                 .highlight_symbol("->")
                 .repeat_highlight_symbol(true);
 
-            frame.render_stateful_widget(list, area, &mut events.state)
+            page = areas[1].height.saturating_sub(2).max(1) as isize;
+            list_area = areas[1];
+            frame.render_stateful_widget(list, areas[1], &mut window)
         })?;
         if event::poll(time::Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Char('q') {
-                    should_quit = true;
-                }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Char('j') {
-                    events.next();
-                }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Char('k') {
-                    events.previous();
+            let event = event::read()?;
+            if let Event::Mouse(mouse) = event {
+                match mouse.kind {
+                    MouseEventKind::ScrollDown => events.next(),
+                    MouseEventKind::ScrollUp => events.previous(),
+                    MouseEventKind::Down(MouseButton::Left) => {
+                        // the checkbox sits past the border and the highlight symbol
+                        let checkbox = list_area.x + 3..list_area.x + 6;
+                        if events.click(list_area, mouse.column, mouse.row)
+                            && checkbox.contains(&mouse.column)
+                        {
+                            if let Some(value) = events.highlighted_item()? {
+                                return_values.toggle(value);
+                            }
+                        }
+                    }
+                    _ => {}
                 }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Down {
-                    events.next();
+            }
+            if let Event::Key(key) = event {
+                if is_interrupt(&key) {
+                    abort();
                 }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Up {
-                    events.previous();
+                if key.kind == event::KeyEventKind::Press && searching {
+                    match keys.command(&key, &[Command::Cancel, Command::Erase, Command::Confirm]) {
+                        Some(Command::Cancel) => {
+                            query.clear();
+                            searching = false;
+                        }
+                        Some(Command::Erase) => {
+                            query.pop();
+                        }
+                        Some(_) => searching = false,
+                        None => match key.code {
+                            // characters are typed into the search, even the ones bound to commands
+                            KeyCode::Char(x)
+                                if !key
+                                    .modifiers
+                                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+                            {
+                                query.push(x)
+                            }
+                            _ => match keys.command(&key, &[Command::Up, Command::Down]) {
+                                Some(Command::Down) => events.next(),
+                                Some(Command::Up) => events.previous(),
+                                _ => {}
+                            },
+                        },
+                    }
+                    events.search(&query)?;
+                    continue;
                 }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Esc {
-                    should_quit = true;
+                if key.kind != event::KeyEventKind::Press {
+                    continue;
                 }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Char(' ') {
-                    match events.choose() {
-                        Some(value) => {
-                            let result_includes_item = return_values
-                                .clone()
-                                .iter()
-                                .map(|y| y.archive_id.as_str())
-                                .collect::<Vec<&str>>()
-                                .contains(&value.archive_id.as_str());
-                            if result_includes_item {
-                                return_values = return_values
-                                    .clone()
-                                    .into_iter()
-                                    .filter(|x| value.archive_id != x.archive_id)
-                                    .collect();
-                            } else {
-                                return_values.push(value);
-                            }
-                        }
-
-                        None => {
+                let command = keys.command(
+                    &key,
+                    &[
+                        Command::Up,
+                        Command::Down,
+                        Command::PageUp,
+                        Command::PageDown,
+                        Command::First,
+                        Command::Last,
+                        Command::Toggle,
+                        Command::SelectAll,
+                        Command::Invert,
+                        Command::Search,
+                        Command::NextMatch,
+                        Command::PreviousMatch,
+                        Command::SortDate,
+                        Command::SortSize,
+                        Command::SortDescription,
+                        Command::Confirm,
+                        Command::Quit,
+                    ],
+                );
+                match command {
+                    Some(Command::Search) => searching = true,
+                    Some(Command::Down | Command::NextMatch) => events.next(),
+                    Some(Command::Up | Command::PreviousMatch) => events.previous(),
+                    Some(Command::Quit) => {
+                        // the first quit drops the search, the selection is kept either way
+                        if query.is_empty() {
                             should_quit = true;
-                            println!("could not match user input")
+                        } else {
+                            query.clear();
+                            events.search(&query)?;
                         }
                     }
-                }
-                if key.kind == event::KeyEventKind::Press && key.code == KeyCode::Enter {
-                    should_quit = true;
+                    Some(Command::Toggle) => {
+                        if let Some(value) = events.highlighted_item()? {
+                            return_values.toggle(value);
+                        }
+                    }
+                    Some(Command::PageDown) => events.move_by(page),
+                    Some(Command::PageUp) => events.move_by(-page),
+                    Some(Command::First) => events.first(),
+                    Some(Command::Last) => events.last(),
+                    Some(Command::SortDate) => events.sort(SortKey::Date)?,
+                    Some(Command::SortSize) => events.sort(SortKey::Size)?,
+                    Some(Command::SortDescription) => events.sort(SortKey::Description)?,
+                    Some(Command::SelectAll) => {
+                        return_values.select_all(events.page(0, events.len())?)
+                    }
+                    Some(Command::Invert) => return_values.invert(events.page(0, events.len())?),
+                    Some(Command::Confirm) => should_quit = true,
+                    _ => {}
                 }
             }
         }
     }
-    release_terminal().expect("Issue releasing the terminal");
-    if return_values.len() >= 1 {
-        return Ok(return_values);
+    drop(terminal);
+    Ok(return_values.into_items())
+}
+
+/// Set while a TUI owns the terminal, so that panics and signals know to hand it back
+static TERMINAL_TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// The terminal of a TUI, handed back to the shell when dropped
+pub struct TerminalGuard(Terminal<CrosstermBackend<Stderr>>);
+
+impl Deref for TerminalGuard {
+    type Target = Terminal<CrosstermBackend<Stderr>>;
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for TerminalGuard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
     }
-    Err(anyhow!("You must select an archive!"))
 }
 
-fn release_terminal() -> Result<(), anyhow::Error> {
-    disable_raw_mode()?;
-    stdout().execute(LeaveAlternateScreen)?;
-    stdout()
-        .execute(LeaveAlternateScreen)
-        .expect("failed releasing terminal");
-    Ok(())
+impl TerminalGuard {
+    /// Report clicks and the scroll wheel as events, given back with the terminal
+    pub fn capture_mouse(&mut self) -> Result<(), anyhow::Error> {
+        stderr().execute(EnableMouseCapture)?;
+        Ok(())
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore_terminal();
+    }
+}
+
+/// Take over the terminal for a TUI, drawn on stderr so that stdout stays clean for archives
+/// streamed with `-o -`
+pub fn enter_terminal() -> Result<TerminalGuard, anyhow::Error> {
+    enable_raw_mode()?;
+    TERMINAL_TAKEN.store(true, AtomicOrdering::SeqCst);
+    stderr().execute(EnterAlternateScreen)?;
+    Ok(TerminalGuard(Terminal::new(CrosstermBackend::new(
+        stderr(),
+    ))?))
+}
+
+/// Leave raw mode and the alternate screen, only once however many times it is called
+pub fn restore_terminal() {
+    if TERMINAL_TAKEN.swap(false, AtomicOrdering::SeqCst) {
+        let _ = disable_raw_mode();
+        let _ = stderr().execute(DisableMouseCapture);
+        let _ = stderr().execute(LeaveAlternateScreen);
+        let _ = stderr().execute(Show);
+    }
+}
+
+/// Make sure panics and SIGINT/SIGTERM never leave the terminal in raw mode or on the alternate
+/// screen
+pub fn install_terminal_hooks() {
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+    tokio::spawn(async {
+        let (Ok(mut interrupt), Ok(mut terminate)) = (
+            signal(SignalKind::interrupt()),
+            signal(SignalKind::terminate()),
+        ) else {
+            return;
+        };
+        let code = tokio::select! {
+            _ = interrupt.recv() => 130,
+            _ = terminate.recv() => 143,
+        };
+        restore_terminal();
+        std::process::exit(code);
+    });
+}
+
+/// Raw mode turns Ctrl-C into a key press instead of a SIGINT
+pub fn is_interrupt(key: &KeyEvent) -> bool {
+    key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+pub fn abort() -> ! {
+    restore_terminal();
+    eprintln!("aborted");
+    std::process::exit(130)
 }
 
 pub struct InfiniteIndeces {
@@ -527,7 +1805,7 @@ impl InfiniteIndeces {
         InfiniteIndeces { value: 0 }
     }
     pub fn next(&mut self) -> usize {
-        self.value = self.value + 1;
+        self.value += 1;
         self.value
     }
 }
@@ -0,0 +1,82 @@
+use crate::config::Config;
+use anyhow::anyhow;
+use ratatui::style::{Color, Modifier, Style};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Colors of the TUI screens as written in the config file, any color name ratatui knows such as
+/// `green`, `lightblue` or `#ff8800`
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// Borders and titles
+    pub border: String,
+    /// Selected rows
+    pub selected: String,
+    /// Unselected rows
+    pub text: String,
+    /// Headers, hints and the names in the details pane
+    pub accent: String,
+    /// The prompt of destructive confirmations
+    pub warning: String,
+    /// Draw without colors, also turned on by the `NO_COLOR` environment variable
+    pub monochrome: bool,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        ThemeConfig {
+            border: String::from("green"),
+            selected: String::from("green"),
+            text: String::from("white"),
+            accent: String::from("yellow"),
+            warning: String::from("red"),
+            monochrome: false,
+        }
+    }
+}
+
+/// https://no-color.org - set and not empty
+fn no_color() -> bool {
+    std::env::var("NO_COLOR").is_ok_and(|x| !x.is_empty())
+}
+
+pub struct Theme {
+    pub border: Style,
+    pub selected: Style,
+    pub text: Style,
+    pub accent: Style,
+    pub warning: Style,
+}
+
+impl Theme {
+    pub fn new(config: &Config) -> Result<Theme, anyhow::Error> {
+        let theme = &config.theme;
+        if theme.monochrome || no_color() {
+            // selected rows still stand out, by their weight and their marker
+            return Ok(Theme {
+                border: Style::default(),
+                selected: Style::default().add_modifier(Modifier::BOLD),
+                text: Style::default(),
+                accent: Style::default(),
+                warning: Style::default(),
+            });
+        }
+        let color = |name: &str| {
+            Color::from_str(name)
+                .map(|x| Style::default().fg(x))
+                .map_err(|_| anyhow!("Unknown color {} in the theme", name))
+        };
+        Ok(Theme {
+            border: color(&theme.border)?,
+            selected: color(&theme.selected)?,
+            text: color(&theme.text)?,
+            accent: color(&theme.accent)?,
+            warning: color(&theme.warning)?,
+        })
+    }
+
+    pub fn is_monochrome(config: &Config) -> bool {
+        config.theme.monochrome || no_color()
+    }
+}